@@ -28,8 +28,15 @@
  * - `args`: the arguments that can be passed to the program. By default no arguments are allowed.
  *   - `true` means that any argument list is allowed.
  *   - `false` means that no arguments are allowed.
- *   - otherwise an array can be configured. Each item is either a string representing the fixed argument value
- *     or a `{ validator: string }` that defines a regex validating the argument value.
+ *   - otherwise an array can be configured. Each item is either a string representing the fixed argument value,
+ *     a `{ validator: string, name?: string }` that defines a regex validating the argument value,
+ *     received by position or, if `name` is set, by name when the arguments are passed as an object,
+ *     a `{ anyOf: string[] }` that defines several regexes, one of which must match the argument value,
+ *     a `{ aliases: Record<string, string[]> }` that maps each allowed value to the arguments it is replaced with,
+ *     a `{ uuid: { version?: number } }` that requires a UUID, of the given `version` if set,
+ *     a `{ email: {} }` that requires an email address,
+ *     a `{ hostname: { allowIp?: boolean } }` that requires a hostname, or an IP address if `allowIp` is set,
+ *     or a `{ fileSizeMax: { bytes: number } }` that requires the path of a file of at most `bytes` bytes.
  *
  * #### Example scope configuration
  *
@@ -77,7 +84,11 @@ declare global {
 interface SpawnOptions {
   /** Current working directory. */
   cwd?: string;
-  /** Environment variables. set to `null` to clear the process env. */
+  /**
+   * Environment variables. set to `null` to clear the process env.
+   *
+   * The variables of a command configuring `env` in its scope must be listed there and pass its validation.
+   */
   env?: Record<string, string>;
   /**
    * Character encoding for stdout/stderr
@@ -121,7 +132,7 @@ interface ChildProcess<O extends IOPayload> {
 async function execute<O extends IOPayload>(
   onEvent: (event: CommandEvent<O>) => void,
   program: string,
-  args: string | string[] = [],
+  args: string | string[] | Record<string, string> = [],
   options?: InternalSpawnOptions
 ): Promise<number> {
   if (typeof args === "object") {
@@ -383,6 +394,8 @@ class Child {
 interface CommandEvents {
   close: TerminatedPayload;
   error: string;
+  /** Progress of the command between 0 and 1, when it is configured with a progress regex. */
+  progress: number;
 }
 
 interface OutputEvents<O extends IOPayload> {
@@ -391,7 +404,7 @@ interface OutputEvents<O extends IOPayload> {
 
 /**
  * The entry point for spawning child processes.
- * It emits the `close` and `error` events.
+ * It emits the `close`, `error` and `progress` events.
  * @example
  * ```typescript
  * import { Command } from '@tauri-apps/plugin-shell';
@@ -414,7 +427,7 @@ class Command<O extends IOPayload> extends EventEmitter<CommandEvents> {
   /** @ignore Program to execute. */
   private readonly program: string;
   /** @ignore Program arguments */
-  private readonly args: string[];
+  private readonly args: string[] | Record<string, string>;
   /** @ignore Spawn options. */
   private readonly options: InternalSpawnOptions;
   /** Event emitter for the `stdout`. Emits the `data` event. */
@@ -433,7 +446,7 @@ class Command<O extends IOPayload> extends EventEmitter<CommandEvents> {
    */
   private constructor(
     program: string,
-    args: string | string[] = [],
+    args: string | string[] | Record<string, string> = [],
     options?: SpawnOptions
   ) {
     super();
@@ -442,15 +455,18 @@ class Command<O extends IOPayload> extends EventEmitter<CommandEvents> {
     this.options = options ?? {};
   }
 
-  static create(program: string, args?: string | string[]): Command<string>;
   static create(
     program: string,
-    args?: string | string[],
+    args?: string | string[] | Record<string, string>
+  ): Command<string>;
+  static create(
+    program: string,
+    args?: string | string[] | Record<string, string>,
     options?: SpawnOptions & { encoding: "raw" }
   ): Command<Uint8Array>;
   static create(
     program: string,
-    args?: string | string[],
+    args?: string | string[] | Record<string, string>,
     options?: SpawnOptions
   ): Command<string>;
 
@@ -465,24 +481,28 @@ class Command<O extends IOPayload> extends EventEmitter<CommandEvents> {
    *
    * @param program The program to execute.
    * It must be configured on `tauri.conf.json > plugins > shell > scope`.
+   * @param args Program arguments, or an object mapping the names of the configured variables to their values.
    */
   static create<O extends IOPayload>(
     program: string,
-    args: string | string[] = [],
+    args: string | string[] | Record<string, string> = [],
     options?: SpawnOptions
   ): Command<O> {
     return new Command(program, args, options);
   }
 
-  static sidecar(program: string, args?: string | string[]): Command<string>;
   static sidecar(
     program: string,
-    args?: string | string[],
+    args?: string | string[] | Record<string, string>
+  ): Command<string>;
+  static sidecar(
+    program: string,
+    args?: string | string[] | Record<string, string>,
     options?: SpawnOptions & { encoding: "raw" }
   ): Command<Uint8Array>;
   static sidecar(
     program: string,
-    args?: string | string[],
+    args?: string | string[] | Record<string, string>,
     options?: SpawnOptions
   ): Command<string>;
 
@@ -497,10 +517,11 @@ class Command<O extends IOPayload> extends EventEmitter<CommandEvents> {
    *
    * @param program The program to execute.
    * It must be configured on `tauri.conf.json > plugins > shell > scope`.
+   * @param args Program arguments, or an object mapping the names of the configured variables to their values.
    */
   static sidecar<O extends IOPayload>(
     program: string,
-    args: string | string[] = [],
+    args: string | string[] | Record<string, string> = [],
     options?: SpawnOptions
   ): Command<O> {
     const instance = new Command<O>(program, args, options);
@@ -531,6 +552,9 @@ class Command<O extends IOPayload> extends EventEmitter<CommandEvents> {
           case "Stderr":
             this.stderr.emit("data", event.payload);
             break;
+          case "Progress":
+            this.emit("progress", event.payload);
+            break;
         }
       },
       this.program,
@@ -609,6 +633,10 @@ interface TerminatedPayload {
   code: number | null;
   /** If the process was terminated by a signal, represents that signal. */
   signal: number | null;
+  /** Why the process terminated. */
+  reason: "exited" | "signaled" | "timeout" | "killed" | "unknown";
+  /** Time elapsed between the spawn of the process and its termination. */
+  duration: { secs: number; nanos: number };
 }
 
 /** Event payload type */
@@ -619,6 +647,7 @@ type CommandEvent<O extends IOPayload> =
   | Event<"Stdout", O>
   | Event<"Stderr", O>
   | Event<"Terminated", TerminatedPayload>
+  | Event<"Progress", number>
   | Event<"Error", string>;
 
 /**
@@ -10,8 +10,8 @@ use tauri::{api::ipc::CallbackFn, Manager, Runtime, State, Window};
 
 use crate::{
     open::Program,
-    process::{CommandEvent, TerminatedPayload},
-    scope::ExecuteArgs,
+    process::{CommandEvent, TerminatedPayload, TimedLine},
+    scope::{self, ExecuteArgs},
     Shell,
 };
 
@@ -29,6 +29,8 @@ enum JSCommandEvent {
     Error(String),
     /// Command process terminated.
     Terminated(TerminatedPayload),
+    /// Progress of the command between 0 and 1, parsed from an output line.
+    Progress(f64),
 }
 
 fn get_event_buffer(line: Vec<u8>, encoding: EncodingWrapper) -> Result<Buffer, FromUtf8Error> {
@@ -48,12 +50,18 @@ impl JSCommandEvent {
         match event {
             CommandEvent::Terminated(payload) => JSCommandEvent::Terminated(payload),
             CommandEvent::Error(error) => JSCommandEvent::Error(error),
-            CommandEvent::Stderr(line) => get_event_buffer(line, encoding)
-                .map(JSCommandEvent::Stderr)
-                .unwrap_or_else(|e| JSCommandEvent::Error(e.to_string())),
-            CommandEvent::Stdout(line) => get_event_buffer(line, encoding)
-                .map(JSCommandEvent::Stdout)
-                .unwrap_or_else(|e| JSCommandEvent::Error(e.to_string())),
+            CommandEvent::Progress(progress) => JSCommandEvent::Progress(progress),
+            // timestamps are a Rust-only feature, the webview receives the plain lines
+            CommandEvent::Stderr(line) | CommandEvent::TimedStderr(TimedLine { line, .. }) => {
+                get_event_buffer(line, encoding)
+                    .map(JSCommandEvent::Stderr)
+                    .unwrap_or_else(|e| JSCommandEvent::Error(e.to_string()))
+            }
+            CommandEvent::Stdout(line) | CommandEvent::TimedStdout(TimedLine { line, .. }) => {
+                get_event_buffer(line, encoding)
+                    .map(JSCommandEvent::Stdout)
+                    .unwrap_or_else(|e| JSCommandEvent::Error(e.to_string()))
+            }
         }
     }
 }
@@ -100,6 +108,13 @@ pub fn execute<R: Runtime>(
     on_event_fn: CallbackFn,
     options: CommandOptions,
 ) -> crate::Result<ChildId> {
+    // the variables passed to commands configuring `env` must be allowed by the scope
+    let restricts_env = options.env.is_some() && shell.scope.restricts_env(&program);
+    let (validated_env, env) = if restricts_env {
+        (options.env, None)
+    } else {
+        (None, options.env)
+    };
     let mut command = if options.sidecar {
         let program = PathBuf::from(program);
         let program_as_string = program.display().to_string();
@@ -116,15 +131,26 @@ pub fn execute<R: Runtime>(
             })
             .cloned();
         if let Some(sidecar) = configured_sidecar {
-            shell
-                .scope
-                .prepare_sidecar(&program.to_string_lossy(), &sidecar, args)?
+            let name = program.to_string_lossy();
+            match validated_env {
+                Some(env) => shell
+                    .scope
+                    .prepare_sidecar_with_env(&name, &sidecar, args, env)?,
+                None => shell.scope.prepare_sidecar(&name, &sidecar, args)?,
+            }
         } else {
             return Err(crate::Error::SidecarNotAllowed(program));
         }
     } else {
-        match shell.scope.prepare(&program, args) {
+        let prepared = match validated_env {
+            Some(env) => shell.scope.prepare_with_env(&program, args, env),
+            None => shell.scope.prepare(&program, args),
+        };
+        match prepared {
             Ok(cmd) => cmd,
+            Err(e @ (scope::Error::EnvNotAllowed { .. } | scope::Error::EnvValidation { .. })) => {
+                return Err(e.into())
+            }
             Err(e) => {
                 #[cfg(debug_assertions)]
                 eprintln!("{e}");
@@ -135,9 +161,9 @@ pub fn execute<R: Runtime>(
     if let Some(cwd) = options.cwd {
         command = command.current_dir(cwd);
     }
-    if let Some(env) = options.env {
+    if let Some(env) = env {
         command = command.envs(env);
-    } else {
+    } else if !restricts_env {
         command = command.env_clear();
     }
     let encoding = match options.encoding {
@@ -2,10 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use serde::{de::Error as DeError, Deserialize, Deserializer};
 
+use crate::open::Program;
+
 /// Configuration for the shell plugin.
 #[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
@@ -17,6 +19,111 @@ pub struct Config {
     /// Open URL with the user's default application.
     #[serde(default)]
     pub open: ShellAllowlistOpen,
+    /// Reveal paths in the file manager.
+    #[serde(default)]
+    pub reveal: ShellAllowlistReveal,
+    /// Program used to open paths when the system has no default application for them, it must be
+    /// one of the `openWith` programs if they are set.
+    #[serde(default)]
+    pub open_default_program: Option<Program>,
+    /// Names of the programs paths may be opened with, e.g. `["firefox"]`, any program if not set.
+    /// An empty list only allows the default application.
+    #[serde(default)]
+    pub open_with: Option<Vec<String>>,
+    /// Fail the plugin initialization when a scoped command path cannot be resolved,
+    /// instead of keeping the configured value as is.
+    #[serde(default)]
+    pub strict_paths: bool,
+    /// Defaults shared by the scoped commands referencing the group by its name.
+    #[serde(default)]
+    pub groups: HashMap<String, ShellCommandGroup>,
+    /// Apply the hardened preset to the scope, see `ScopeConfig::hardened`.
+    #[serde(default)]
+    pub hardened: bool,
+    /// The maximum number of scoped commands, defaults to `scope::DEFAULT_MAX_COMMANDS`.
+    /// A longer `scope` list is rejected before any of its regexes is compiled.
+    #[serde(default)]
+    pub max_commands: Option<usize>,
+}
+
+/// Defaults inherited by the scoped commands of a group, each command may override them.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ShellCommandGroup {
+    /// The command name used by the commands that do not set their own `cmd`.
+    #[serde(rename = "cmd")]
+    pub command: Option<PathBuf>,
+    /// The execution policy whose fields are used by the commands that do not set them in their
+    /// own `policy`.
+    pub policy: Option<ShellExecutionPolicy>,
+    /// The disk I/O priority used by the commands that do not set their own `ioPriority`.
+    pub io_priority: Option<ShellIoPriority>,
+    /// The working directory used by the commands that do not set their own `cwd`.
+    pub cwd: Option<ShellCwdMode>,
+}
+
+/// A constraint between the numeric values of two arguments of a command, referenced by position.
+///
+/// e.g. `{ "left": 1, "relation": "le", "right": 3 }` requires the second argument to be at most
+/// the fourth one.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShellCrossValidator {
+    /// The position of the argument on the left side of the relation.
+    pub left: usize,
+    /// The relation the values must have: `lt`, `le`, `eq`, `ne`, `ge` or `gt`.
+    pub relation: ShellRelation,
+    /// The position of the argument on the right side of the relation.
+    pub right: usize,
+}
+
+/// A relation between two values of a [`ShellCrossValidator`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellRelation {
+    /// Less than.
+    Lt,
+    /// Less than or equal to.
+    Le,
+    /// Equal to.
+    Eq,
+    /// Not equal to.
+    Ne,
+    /// Greater than or equal to.
+    Ge,
+    /// Greater than.
+    Gt,
+}
+
+/// Handling of a single string passed to a command allowed to be executed by the webview API with
+/// a list of arguments.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub enum ShellSingleArgMode {
+    /// The string is rejected.
+    #[default]
+    Reject,
+    /// The string is split into arguments like a POSIX shell does, e.g. `-m "a message"` into
+    /// `["-m", "a message"]`, then validated.
+    SplitShell,
+}
+
+/// Working directory of a command allowed to be executed by the webview API.
+///
+/// e.g. `"inherit"`, `"appDir"`, `"resourceDir"` or `{ "path": "$HOME/projects" }`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub enum ShellCwdMode {
+    /// The working directory of the app.
+    #[default]
+    Inherit,
+    /// The app data directory, `$APPDATA`.
+    AppDir,
+    /// The resource directory, `$RESOURCE`.
+    ResourceDir,
+    /// A path, which can start with a variable that resolves to a system base directory like
+    /// the command name.
+    Path(PathBuf),
 }
 
 /// A command allowed to be executed by the webview API.
@@ -42,6 +149,60 @@ pub struct ShellAllowedCommand {
 
     /// If this command is a sidecar command.
     pub sidecar: bool,
+
+    /// If this command can be spawned as an interactive session, with its stdin kept open.
+    pub allow_stdin: bool,
+
+    /// If only one instance of this command can run at a time.
+    pub singleton: bool,
+
+    /// How the command is executed: its timeout, retries and successful exit codes.
+    pub policy: ShellExecutionPolicy,
+
+    /// The disk I/O priority the command runs with, e.g. `"idle"` or `{ "bestEffort": 7 }`.
+    ///
+    /// Ignored on Windows.
+    pub io_priority: Option<ShellIoPriority>,
+
+    /// The name of the group this command inherits its defaults from.
+    pub group: Option<String>,
+
+    /// Capability tags of the command, e.g. `["filesystem", "dangerous"]`.
+    ///
+    /// The command is denied while any of its tags is disabled.
+    pub tags: Vec<String>,
+
+    /// The working directory of the command. Defaults to the working directory of the app.
+    pub cwd: ShellCwdMode,
+
+    /// Constraints between the values of two arguments, checked after each argument is validated.
+    pub cross_validators: Vec<ShellCrossValidator>,
+
+    /// How a single string passed to a command with a list of arguments is handled.
+    pub single_arg_mode: ShellSingleArgMode,
+
+    /// A marker argument, e.g. `--`, after which the arguments are passed through unchanged.
+    ///
+    /// Passed through arguments are not validated against `args`, but must not contain control
+    /// characters. The marker is only recognized after the positions declared in `args`.
+    pub passthrough_after: Option<String>,
+
+    /// [regex] parsing the progress of the command from its output lines, with a `percent` named
+    /// capture group, or `current` and `total` ones.
+    ///
+    /// [regex]: https://docs.rs/regex/latest/regex/#syntax
+    pub progress_regex: Option<String>,
+
+    /// Reject the calls passing more arguments than the positions declared in `args`.
+    ///
+    /// Extra arguments are then never appended, and `passthroughAfter` is ignored.
+    pub strict_positions: bool,
+
+    /// The environment variables the caller may set, mapped to the [regex] validating their
+    /// values. The caller may not set any environment variable if not set.
+    ///
+    /// [regex]: https://docs.rs/regex/latest/regex/#syntax
+    pub env: Option<HashMap<String, String>>,
 }
 
 impl<'de> Deserialize<'de> for ShellAllowedCommand {
@@ -58,11 +219,37 @@ impl<'de> Deserialize<'de> for ShellAllowedCommand {
             args: ShellAllowedArgs,
             #[serde(default)]
             sidecar: bool,
+            #[serde(default, rename = "allowStdin")]
+            allow_stdin: bool,
+            #[serde(default)]
+            singleton: bool,
+            #[serde(default)]
+            policy: ShellExecutionPolicy,
+            #[serde(default, rename = "ioPriority")]
+            io_priority: Option<ShellIoPriority>,
+            #[serde(default)]
+            group: Option<String>,
+            #[serde(default)]
+            tags: Vec<String>,
+            #[serde(default)]
+            cwd: ShellCwdMode,
+            #[serde(default, rename = "crossValidators")]
+            cross_validators: Vec<ShellCrossValidator>,
+            #[serde(default, rename = "singleArgMode")]
+            single_arg_mode: ShellSingleArgMode,
+            #[serde(default, rename = "passthroughAfter")]
+            passthrough_after: Option<String>,
+            #[serde(default, rename = "progressRegex")]
+            progress_regex: Option<String>,
+            #[serde(default, rename = "strictPositions")]
+            strict_positions: bool,
+            #[serde(default)]
+            env: Option<HashMap<String, String>>,
         }
 
         let config = InnerShellAllowedCommand::deserialize(deserializer)?;
 
-        if !config.sidecar && config.command.is_none() {
+        if !config.sidecar && config.command.is_none() && config.group.is_none() {
             return Err(DeError::custom(
                 "The shell scope `command` value is required.",
             ));
@@ -73,10 +260,56 @@ impl<'de> Deserialize<'de> for ShellAllowedCommand {
             command: config.command.unwrap_or_default(),
             args: config.args,
             sidecar: config.sidecar,
+            allow_stdin: config.allow_stdin,
+            singleton: config.singleton,
+            policy: config.policy,
+            io_priority: config.io_priority,
+            group: config.group,
+            tags: config.tags,
+            cwd: config.cwd,
+            cross_validators: config.cross_validators,
+            single_arg_mode: config.single_arg_mode,
+            passthrough_after: config.passthrough_after,
+            progress_regex: config.progress_regex,
+            strict_positions: config.strict_positions,
+            env: config.env,
         })
     }
 }
 
+/// Execution controls of a command allowed to be executed by the webview API.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ShellExecutionPolicy {
+    /// Kill the command if it is still running after this many milliseconds.
+    pub timeout_ms: Option<u64>,
+    /// How many times an unsuccessful execution is retried when the output is collected.
+    /// Defaults to 0.
+    pub retries: Option<u32>,
+    /// Delay in milliseconds before the first retry, doubled for each following retry.
+    /// Defaults to 500.
+    pub backoff_ms: Option<u64>,
+    /// The exit codes considered a success. Defaults to `[0]`.
+    pub success_codes: Option<Vec<i32>>,
+    /// The Unix signals whose termination is considered a success, e.g. `[15]` for `SIGTERM`.
+    /// Ignored on other platforms.
+    pub expected_signals: Option<Vec<i32>>,
+    /// Maximum number of bytes collected from stdout, and from stderr.
+    pub max_output: Option<usize>,
+}
+
+/// Disk I/O priority of a command allowed to be executed by the webview API.
+///
+/// Applied on Linux and macOS, Windows accepts but ignores it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub enum ShellIoPriority {
+    /// The command only gets disk time when no other process needs it.
+    Idle,
+    /// Best-effort scheduling at a level from 0 (highest) to 7 (lowest).
+    BestEffort(u8),
+}
+
 /// A set of command arguments allowed to be executed by the webview API.
 ///
 /// A value of `true` will allow any arguments to be passed to the command. `false` will disable all
@@ -117,9 +350,116 @@ pub enum ShellAllowedArg {
         ///
         /// [regex]: https://docs.rs/regex/latest/regex/#syntax
         validator: String,
+
+        /// The maximum length in bytes of the value passed to this variable.
+        ///
+        /// Longer values are rejected before the `validator` regex is evaluated.
+        /// This only bounds this argument, not the length of the whole command line.
+        #[serde(default, rename = "maxLen")]
+        max_len: Option<usize>,
+
+        /// Named capture groups of the `validator` regex that must capture a non-empty value.
+        ///
+        /// e.g. `["name", "tag"]` with the `^(?P<name>\w*):(?P<tag>\w*)$` validator rejects `name:`.
+        #[serde(default, rename = "requireCaptures")]
+        require_captures: Vec<String>,
+
+        /// Convert the validated value to a path with the platform-native separators.
+        ///
+        /// Both `/` and `\` are accepted as separators, and `.` and `..` components are resolved
+        /// textually: the filesystem is not accessed, so symbolic links are not followed.
+        #[serde(default, rename = "toNativePath")]
+        to_native_path: bool,
+
+        /// The name of the variable, e.g. `input`, to receive its value by name instead of by
+        /// position when the command is called with an object of arguments.
+        #[serde(default)]
+        name: Option<String>,
+    },
+
+    /// A variable that is set while calling the command from the webview API, and must match at
+    /// least one of several validators.
+    AnyOf {
+        /// [regex] validators the passed value is checked against.
+        ///
+        /// The argument value is accepted as soon as it matches one of the `anyOf` regexes.
+        ///
+        /// [regex]: https://docs.rs/regex/latest/regex/#syntax
+        #[serde(rename = "anyOf")]
+        any_of: Vec<String>,
+    },
+
+    /// A variable that is set while calling the command from the webview API, and is replaced by the
+    /// arguments it maps to.
+    Aliases {
+        /// The values allowed to be passed, mapped to the arguments the command is called with.
+        ///
+        /// e.g. `{ "high": ["-vvv"], "low": ["-v"] }` accepts `high` and `low`, calling the command
+        /// with `-vvv` or `-v` respectively.
+        aliases: HashMap<String, Vec<String>>,
+    },
+
+    /// A variable that is set while calling the command from the webview API, and must be a UUID.
+    Uuid {
+        /// The UUID validation options, e.g. `{ "version": 4 }`.
+        uuid: ShellAllowedUuid,
+    },
+
+    /// A variable that is set while calling the command from the webview API, and must be an
+    /// email address.
+    Email {
+        /// The email validation options, currently none, e.g. `{}`.
+        email: ShellAllowedEmail,
+    },
+
+    /// A variable that is set while calling the command from the webview API, and must be a
+    /// hostname.
+    Hostname {
+        /// The hostname validation options, e.g. `{ "allowIp": true }`.
+        hostname: ShellAllowedHostname,
+    },
+
+    /// A variable that is set while calling the command from the webview API, and must be the path
+    /// of a file no larger than a limit. Relative paths are resolved against the `cwd` of the
+    /// command.
+    FileSizeMax {
+        /// The file size validation options, e.g. `{ "bytes": 1048576 }`.
+        #[serde(rename = "fileSizeMax")]
+        file_size_max: ShellAllowedFileSize,
     },
 }
 
+/// Options of a [`ShellAllowedArg::Uuid`] variable.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShellAllowedUuid {
+    /// The UUID version the value must have. Any version is accepted if not set.
+    #[serde(default)]
+    pub version: Option<u8>,
+}
+
+/// Options of a [`ShellAllowedArg::Email`] variable.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShellAllowedEmail {}
+
+/// Options of a [`ShellAllowedArg::FileSizeMax`] variable.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ShellAllowedFileSize {
+    /// The maximum size of the file in bytes.
+    pub bytes: u64,
+}
+
+/// Options of a [`ShellAllowedArg::Hostname`] variable.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ShellAllowedHostname {
+    /// Whether IPv4 and IPv6 addresses are accepted as well. Defaults to `false`.
+    #[serde(default)]
+    pub allow_ip: bool,
+}
+
 /// Shell scope definition.
 /// It is a list of command names and associated CLI arguments that restrict the API access from the webview.
 #[derive(Debug, Default, PartialEq, Eq, Clone, Deserialize)]
@@ -148,3 +488,21 @@ impl Default for ShellAllowlistOpen {
         Self::Flag(false)
     }
 }
+
+/// Defines the `shell > reveal` api scope.
+#[derive(Debug, PartialEq, Eq, Clone, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+#[non_exhaustive]
+pub enum ShellAllowlistReveal {
+    /// If revealing any path in the file manager is allowed.
+    Flag(bool),
+
+    /// Allow revealing the paths matching a custom regex in the file manager.
+    Validate(String),
+}
+
+impl Default for ShellAllowlistReveal {
+    fn default() -> Self {
+        Self::Flag(false)
+    }
+}
@@ -10,6 +10,14 @@ use serde::{Serialize, Serializer};
 pub enum Error {
     #[error(transparent)]
     Io(#[from] std::io::Error),
+    /// The command could not be spawned.
+    #[error("failed to spawn `{spawned}`: {source}")]
+    Spawn {
+        /// The invocation that failed.
+        spawned: crate::process::SpawnedInfo,
+        #[source]
+        source: std::io::Error,
+    },
     #[error("current executable path has no parent")]
     CurrentExeHasNoParent,
     #[error("unknown program {0}")]
@@ -5,11 +5,15 @@
 use std::{
     collections::HashMap,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
-use process::{Command, CommandChild};
+use process::{Command, CommandChild, ExecutionPolicy, IoPriority};
 use regex::Regex;
-use scope::{Scope, ScopeAllowedCommand, ScopeConfig};
+use scope::{
+    CommandGroupDefaults, CrossValidator, CwdMode, Relation, Scope, ScopeAllowedCommand,
+    ScopeConfig, SingleArgMode,
+};
 use tauri::{
     plugin::{Builder, TauriPlugin},
     AppHandle, Manager, RunEvent, Runtime,
@@ -20,9 +24,15 @@ mod config;
 mod error;
 mod open;
 pub mod process;
-mod scope;
+pub mod scope;
 
-use config::{Config, ShellAllowedArg, ShellAllowedArgs, ShellAllowlistOpen, ShellAllowlistScope};
+use config::{
+    Config, ShellAllowedArg, ShellAllowedArgs, ShellAllowlistOpen, ShellAllowlistReveal,
+    ShellAllowlistScope, ShellCommandGroup, ShellCrossValidator, ShellCwdMode,
+    ShellExecutionPolicy, ShellIoPriority, ShellRelation, ShellSingleArgMode,
+};
+#[cfg(feature = "signed-config")]
+pub use ed25519_dalek;
 pub use error::Error;
 type Result<T> = std::result::Result<T, Error>;
 type ChildStore = Arc<Mutex<HashMap<u32, CommandChild>>>;
@@ -48,12 +58,52 @@ impl<R: Runtime> Shell<R> {
         Command::new_sidecar(program)
     }
 
+    /// The scope validating the commands and paths allowed by the plugin configuration.
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
     /// Open a (url) path with a default or specific browser opening program.
     ///
     /// See [`crate::api::shell::open`] for how it handles security-related measures.
     pub fn open(&self, path: impl Into<String>, with: Option<open::Program>) -> Result<()> {
         open::open(&self.scope, path.into(), with).map_err(Into::into)
     }
+
+    /// Reveal a path in the file manager, see [`Scope::reveal`].
+    pub fn reveal(&self, path: impl Into<String>) -> Result<()> {
+        self.scope.reveal(&path.into()).map_err(Into::into)
+    }
+
+    /// Open a path, or reveal it in the file manager if it cannot be opened, see
+    /// [`Scope::open_or_reveal`].
+    pub fn open_or_reveal(&self, path: impl Into<String>) -> Result<()> {
+        self.scope.open_or_reveal(&path.into()).map_err(Into::into)
+    }
+
+    /// Open a (url) path like [`Self::open`] once its host is reachable, see [`Scope::open_checked`].
+    #[cfg(feature = "open-checked")]
+    pub fn open_checked(
+        &self,
+        url: impl Into<String>,
+        with: Option<open::Program>,
+        head_timeout: Option<std::time::Duration>,
+    ) -> Result<()> {
+        self.scope
+            .open_checked(&url.into(), with, head_timeout)
+            .map_err(Into::into)
+    }
+
+    /// Open a (url) path like [`Self::open`], returning the status the launcher reported.
+    pub fn open_status(
+        &self,
+        path: impl Into<String>,
+        with: Option<open::Program>,
+    ) -> Result<scope::OpenStatus> {
+        self.scope
+            .open_status(&path.into(), with)
+            .map_err(Into::into)
+    }
 }
 
 pub trait ShellExt<R: Runtime> {
@@ -67,6 +117,16 @@ impl<R: Runtime, T: Manager<R>> ShellExt<R> for T {
 }
 
 pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
+    builder(None)
+}
+
+/// Initializes the plugin with a scope provided by the app instead of the one in the plugin
+/// configuration, e.g. one loaded with [`ScopeConfig::from_file_signed`].
+pub fn init_with_scope<R: Runtime>(scope: ScopeConfig) -> TauriPlugin<R, Option<Config>> {
+    builder(Some(scope))
+}
+
+fn builder<R: Runtime>(scope: Option<ScopeConfig>) -> TauriPlugin<R, Option<Config>> {
     let mut init_script = include_str!("init.js").to_string();
     init_script.push_str(include_str!("api-iife.js"));
 
@@ -81,10 +141,14 @@ pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
         .setup(|app, api| {
             let default_config = Config::default();
             let config = api.config().as_ref().unwrap_or(&default_config);
+            let scope = match scope {
+                Some(scope) => scope,
+                None => shell_scope(config)?,
+            };
             app.manage(Shell {
                 app: app.clone(),
                 children: Default::default(),
-                scope: Scope::new(app, shell_scope(config.scope.clone(), &config.open)),
+                scope: Scope::new(app, scope)?,
             });
             Ok(())
         })
@@ -103,56 +167,395 @@ pub fn init<R: Runtime>() -> TauriPlugin<R, Option<Config>> {
         .build()
 }
 
-fn shell_scope(scope: ShellAllowlistScope, open: &ShellAllowlistOpen) -> ScopeConfig {
-    let shell_scopes = get_allowed_clis(scope);
+/// Converts the plugin configuration to a scope config, failing with an
+/// [`scope::Error::InvalidConfig`] error on invalid values like regexes that do not compile.
+fn shell_scope(config: &Config) -> std::result::Result<ScopeConfig, scope::Error> {
+    // checked before any regex is compiled, which is the cost the limit bounds
+    let max_commands = config.max_commands.unwrap_or(scope::DEFAULT_MAX_COMMANDS);
+    if config.scope.0.len() > max_commands {
+        return Err(scope::Error::TooManyCommands {
+            count: config.scope.0.len(),
+            limit: max_commands,
+        });
+    }
+    let shell_scopes = get_allowed_clis(config.scope.clone(), &config.groups)?;
 
-    let shell_scope_open = match open {
+    let shell_scope_open = match &config.open {
         ShellAllowlistOpen::Flag(false) => None,
         ShellAllowlistOpen::Flag(true) => {
             Some(Regex::new(r#"^((mailto:\w+)|(tel:\w+)|(https?://\w+)).+"#).unwrap())
         }
-        ShellAllowlistOpen::Validate(validator) => {
-            let validator =
-                Regex::new(validator).unwrap_or_else(|e| panic!("invalid regex {validator}: {e}"));
-            Some(validator)
-        }
+        ShellAllowlistOpen::Validate(validator) => Some(scope::compile_regex(validator)?),
+    };
+
+    let shell_scope_reveal = match &config.reveal {
+        ShellAllowlistReveal::Flag(false) => None,
+        ShellAllowlistReveal::Flag(true) => Some(Regex::new(".*").unwrap()),
+        ShellAllowlistReveal::Validate(validator) => Some(scope::compile_regex(validator)?),
     };
 
-    ScopeConfig {
+    let scope = ScopeConfig {
         open: shell_scope_open,
+        reveal: shell_scope_reveal,
+        open_default_program: config.open_default_program,
+        open_with: config.open_with.clone(),
         scopes: shell_scopes,
+        strict_paths: config.strict_paths,
+        max_commands,
+        groups: config
+            .groups
+            .iter()
+            .map(|(name, group)| (name.clone(), group_defaults(group.clone())))
+            .collect(),
+    };
+    if config.hardened {
+        ScopeConfig::hardened(scope)
+    } else {
+        Ok(scope)
     }
 }
 
-fn get_allowed_clis(scope: ShellAllowlistScope) -> HashMap<String, ScopeAllowedCommand> {
+fn get_allowed_clis(
+    scope: ShellAllowlistScope,
+    groups: &HashMap<String, ShellCommandGroup>,
+) -> std::result::Result<HashMap<String, ScopeAllowedCommand>, scope::Error> {
     scope
         .0
         .into_iter()
         .map(|scope| {
+            // an unknown group is reported when the scope is created
+            let policy = match scope
+                .group
+                .as_ref()
+                .and_then(|group| groups.get(group)?.policy.as_ref())
+            {
+                Some(defaults) => merge_policy(scope.policy, defaults),
+                None => scope.policy,
+            };
             let args = match scope.args {
                 ShellAllowedArgs::Flag(true) => None,
                 ShellAllowedArgs::Flag(false) => Some(Vec::new()),
                 ShellAllowedArgs::List(list) => {
-                    let list = list.into_iter().map(|arg| match arg {
-                        ShellAllowedArg::Fixed(fixed) => scope::ScopeAllowedArg::Fixed(fixed),
-                        ShellAllowedArg::Var { validator } => {
-                            let validator = Regex::new(&validator)
-                                .unwrap_or_else(|e| panic!("invalid regex {validator}: {e}"));
-                            scope::ScopeAllowedArg::Var { validator }
-                        }
+                    let list = list.into_iter().map(|arg| {
+                        Ok(match arg {
+                            ShellAllowedArg::Fixed(fixed) => scope::ScopeAllowedArg::Fixed(fixed),
+                            ShellAllowedArg::Var {
+                                validator,
+                                max_len,
+                                require_captures,
+                                to_native_path,
+                                name,
+                            } => {
+                                let validator = scope::compile_regex(&validator)?;
+                                if let Some(name) = require_captures.iter().find(|name| {
+                                    !validator.capture_names().any(|n| n == Some(name))
+                                }) {
+                                    return Err(scope::Error::InvalidConfig(format!(
+                                        "regex {validator} has no capture group named {name}"
+                                    )));
+                                }
+                                scope::ScopeAllowedArg::Var {
+                                    validator,
+                                    max_len,
+                                    require_captures,
+                                    to_native_path,
+                                    name,
+                                }
+                            }
+                            ShellAllowedArg::AnyOf { any_of } => {
+                                let validators = any_of
+                                    .iter()
+                                    .map(|validator| scope::compile_regex(validator))
+                                    .collect::<std::result::Result<_, _>>()?;
+                                scope::ScopeAllowedArg::AnyOf(validators)
+                            }
+                            ShellAllowedArg::Aliases { aliases } => {
+                                scope::ScopeAllowedArg::Alias(aliases)
+                            }
+                            ShellAllowedArg::Uuid { uuid } => scope::ScopeAllowedArg::Uuid {
+                                version: uuid.version,
+                            },
+                            ShellAllowedArg::Email { .. } => scope::ScopeAllowedArg::Email,
+                            ShellAllowedArg::Hostname { hostname } => {
+                                scope::ScopeAllowedArg::Hostname {
+                                    allow_ip: hostname.allow_ip,
+                                }
+                            }
+                            ShellAllowedArg::FileSizeMax { file_size_max } => {
+                                scope::ScopeAllowedArg::FileSizeMax {
+                                    bytes: file_size_max.bytes,
+                                }
+                            }
+                        })
                     });
-                    Some(list.collect())
+                    Some(list.collect::<std::result::Result<_, _>>()?)
                 }
             };
 
-            (
+            Ok((
                 scope.name,
                 ScopeAllowedCommand {
                     command: scope.command,
                     args,
                     sidecar: scope.sidecar,
+                    allow_stdin: scope.allow_stdin,
+                    singleton: scope.singleton,
+                    policy: Some(execution_policy(policy)),
+                    io_priority: scope.io_priority.map(io_priority),
+                    group: scope.group,
+                    tags: scope.tags,
+                    cwd: cwd_mode(scope.cwd),
+                    cross_validators: scope
+                        .cross_validators
+                        .into_iter()
+                        .map(cross_validator)
+                        .collect(),
+                    single_arg_mode: match scope.single_arg_mode {
+                        ShellSingleArgMode::Reject => SingleArgMode::Reject,
+                        ShellSingleArgMode::SplitShell => SingleArgMode::SplitShell,
+                    },
+                    passthrough_after: scope.passthrough_after,
+                    progress_regex: scope
+                        .progress_regex
+                        .as_deref()
+                        .map(progress_regex)
+                        .transpose()?,
+                    strict_positions: scope.strict_positions,
+                    env: scope
+                        .env
+                        .map(|env| {
+                            env.into_iter()
+                                .map(|(name, validator)| {
+                                    Ok((name, scope::compile_regex(&validator)?))
+                                })
+                                .collect::<std::result::Result<_, scope::Error>>()
+                        })
+                        .transpose()?,
+                    hardening: Default::default(),
                 },
-            )
+            ))
         })
         .collect()
 }
+
+fn group_defaults(group: ShellCommandGroup) -> CommandGroupDefaults {
+    CommandGroupDefaults {
+        command: group.command,
+        policy: group.policy.map(execution_policy),
+        io_priority: group.io_priority.map(io_priority),
+        cwd: group.cwd.map(cwd_mode),
+    }
+}
+
+/// Fills the fields the command policy does not set with the ones of its group policy.
+fn merge_policy(
+    policy: ShellExecutionPolicy,
+    defaults: &ShellExecutionPolicy,
+) -> ShellExecutionPolicy {
+    ShellExecutionPolicy {
+        timeout_ms: policy.timeout_ms.or(defaults.timeout_ms),
+        retries: policy.retries.or(defaults.retries),
+        backoff_ms: policy.backoff_ms.or(defaults.backoff_ms),
+        success_codes: policy
+            .success_codes
+            .or_else(|| defaults.success_codes.clone()),
+        expected_signals: policy
+            .expected_signals
+            .or_else(|| defaults.expected_signals.clone()),
+        max_output: policy.max_output.or(defaults.max_output),
+    }
+}
+
+fn cross_validator(validator: ShellCrossValidator) -> CrossValidator {
+    CrossValidator {
+        left: validator.left,
+        relation: match validator.relation {
+            ShellRelation::Lt => Relation::Lt,
+            ShellRelation::Le => Relation::Le,
+            ShellRelation::Eq => Relation::Eq,
+            ShellRelation::Ne => Relation::Ne,
+            ShellRelation::Ge => Relation::Ge,
+            ShellRelation::Gt => Relation::Gt,
+        },
+        right: validator.right,
+    }
+}
+
+fn progress_regex(pattern: &str) -> std::result::Result<Regex, scope::Error> {
+    let regex = scope::compile_regex(pattern)?;
+    let has = |name| regex.capture_names().any(|n| n == Some(name));
+    if !(has("percent") || has("current") && has("total")) {
+        return Err(scope::Error::InvalidConfig(format!(
+            "regex {pattern} has neither a `percent` nor `current` and `total` capture groups"
+        )));
+    }
+    Ok(regex)
+}
+
+fn cwd_mode(cwd: ShellCwdMode) -> CwdMode {
+    match cwd {
+        ShellCwdMode::Inherit => CwdMode::Inherit,
+        ShellCwdMode::AppDir => CwdMode::AppDir,
+        ShellCwdMode::ResourceDir => CwdMode::ResourceDir,
+        ShellCwdMode::Path(path) => CwdMode::Path(path),
+    }
+}
+
+fn io_priority(priority: ShellIoPriority) -> IoPriority {
+    match priority {
+        ShellIoPriority::Idle => IoPriority::Idle,
+        ShellIoPriority::BestEffort(level) => IoPriority::BestEffort(level),
+    }
+}
+
+fn execution_policy(policy: ShellExecutionPolicy) -> ExecutionPolicy {
+    let default = ExecutionPolicy::default();
+    ExecutionPolicy {
+        timeout: policy.timeout_ms.map(Duration::from_millis),
+        retries: policy.retries.unwrap_or(default.retries),
+        backoff: policy
+            .backoff_ms
+            .map(Duration::from_millis)
+            .unwrap_or(default.backoff),
+        success_codes: policy.success_codes.unwrap_or(default.success_codes),
+        expected_signals: policy.expected_signals.unwrap_or(default.expected_signals),
+        max_output: policy.max_output,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(json: serde_json::Value) -> Config {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_group_policy_merge() {
+        let scope = shell_scope(&config(serde_json::json!({
+            "groups": {
+                "tools": { "policy": { "timeoutMs": 1000, "retries": 2, "successCodes": [0, 1] } }
+            },
+            "scope": [
+                { "name": "inherit", "cmd": "a", "group": "tools" },
+                { "name": "partial", "cmd": "b", "group": "tools", "policy": { "retries": 0 } },
+                { "name": "ungrouped", "cmd": "c", "policy": { "retries": 3 } }
+            ]
+        })))
+        .unwrap();
+        let policy = |name: &str| scope.scopes[name].policy.clone().unwrap();
+
+        let group = ExecutionPolicy {
+            timeout: Some(Duration::from_millis(1000)),
+            retries: 2,
+            success_codes: vec![0, 1],
+            ..Default::default()
+        };
+        assert_eq!(policy("inherit"), group);
+        // an explicit default value still overrides the group
+        assert_eq!(
+            policy("partial"),
+            ExecutionPolicy {
+                retries: 0,
+                ..group
+            }
+        );
+        assert_eq!(
+            policy("ungrouped"),
+            ExecutionPolicy {
+                retries: 3,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_require_unknown_capture() {
+        let requiring = |require: &str| {
+            shell_scope(&config(serde_json::json!({
+                "scope": [{
+                    "name": "tag",
+                    "cmd": "docker",
+                    "args": [{ "validator": "^(?P<name>\\w+):(?P<tag>\\w*)$", "requireCaptures": [require] }]
+                }]
+            })))
+        };
+        assert!(requiring("tag").is_ok());
+        assert!(matches!(
+            requiring("digest"),
+            Err(scope::Error::InvalidConfig(message)) if message.ends_with("named digest")
+        ));
+    }
+
+    #[test]
+    fn test_cwd_modes() {
+        let scope = shell_scope(&config(serde_json::json!({
+            "groups": { "tools": { "cwd": "resourceDir" } },
+            "scope": [
+                { "name": "default", "cmd": "a" },
+                { "name": "app", "cmd": "b", "cwd": "appDir" },
+                { "name": "path", "cmd": "c", "cwd": { "path": "$HOME/projects" } },
+                { "name": "grouped", "cmd": "d", "group": "tools" },
+                { "name": "own", "cmd": "e", "group": "tools", "cwd": "appDir" }
+            ]
+        })))
+        .unwrap();
+        let cwd = |name: &str| scope.scopes[name].cwd.clone();
+
+        assert_eq!(cwd("default"), CwdMode::Inherit);
+        assert_eq!(cwd("app"), CwdMode::AppDir);
+        assert_eq!(cwd("path"), CwdMode::Path("$HOME/projects".into()));
+        // the group defaults are merged when the scope is created
+        assert_eq!(cwd("grouped"), CwdMode::Inherit);
+        assert_eq!(cwd("own"), CwdMode::AppDir);
+        assert_eq!(scope.groups["tools"].cwd, Some(CwdMode::ResourceDir));
+    }
+
+    #[test]
+    fn test_max_commands() {
+        let commands = |count: usize| {
+            (0..count)
+                .map(|i| serde_json::json!({ "name": format!("cmd{i}"), "cmd": "echo" }))
+                .collect::<Vec<_>>()
+        };
+        let limited =
+            |count| config(serde_json::json!({ "maxCommands": 2, "scope": commands(count) }));
+        assert!(shell_scope(&limited(2)).is_ok());
+        assert!(matches!(
+            shell_scope(&limited(3)),
+            Err(scope::Error::TooManyCommands { count: 3, limit: 2 })
+        ));
+        // the regexes of the commands are not even compiled
+        let invalid = config(serde_json::json!({
+            "maxCommands": 1,
+            "scope": [
+                { "name": "a", "cmd": "echo", "args": [{ "validator": "(" }] },
+                { "name": "b", "cmd": "echo" }
+            ]
+        }));
+        assert!(matches!(
+            shell_scope(&invalid),
+            Err(scope::Error::TooManyCommands { .. })
+        ));
+    }
+
+    #[test]
+    fn test_open_default_program() {
+        let scope = shell_scope(&config(serde_json::json!({
+            "openDefaultProgram": "Google Chrome",
+            "openWith": ["chrome"]
+        })))
+        .unwrap();
+        assert_eq!(scope.open_default_program, Some(open::Program::Chrome));
+        assert!(shell_scope(&Config::default())
+            .unwrap()
+            .open_default_program
+            .is_none());
+
+        let unknown = serde_json::from_value::<Config>(serde_json::json!({
+            "openDefaultProgram": "notepad"
+        }));
+        assert!(unknown.is_err());
+    }
+}
@@ -10,6 +10,7 @@ use crate::scope::Scope;
 use std::str::FromStr;
 
 /// Program to use on the [`open()`] call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Program {
     /// Use the `open` program.
     Open,
@@ -3,14 +3,23 @@
 // SPDX-License-Identifier: MIT
 
 use std::{
-    collections::HashMap,
-    io::{BufReader, Write},
+    collections::{HashMap, VecDeque},
+    fmt,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
     path::PathBuf,
     process::{Command as StdCommand, Stdio},
-    sync::{Arc, RwLock},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex, RwLock,
+    },
     thread::spawn,
+    time::{Duration, Instant, SystemTime},
 };
 
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::os::unix::process::CommandExt as _;
 #[cfg(unix)]
 use std::os::unix::process::ExitStatusExt;
 #[cfg(windows)]
@@ -24,6 +33,7 @@ use tauri::async_runtime::{block_on as block_on_task, channel, Receiver, Sender}
 
 pub use encoding_rs::Encoding;
 use os_pipe::{pipe, PipeReader, PipeWriter};
+use regex::Regex;
 use serde::Serialize;
 use shared_child::SharedChild;
 use tauri::utils::platform;
@@ -35,10 +45,38 @@ pub struct TerminatedPayload {
     pub code: Option<i32>,
     /// If the process was terminated by a signal, represents that signal.
     pub signal: Option<i32>,
+    /// Why the process terminated.
+    pub reason: TerminationReason,
+    /// Time elapsed between the spawn of the process and its termination.
+    pub duration: Duration,
+}
+
+/// Why a process terminated, reported in the [`TerminatedPayload`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum TerminationReason {
+    /// The process exited by itself with an exit code.
+    Exited,
+    /// The process was terminated by a signal it did not receive from the plugin.
+    Signaled,
+    /// The process was killed because it exceeded its timeout.
+    Timeout,
+    /// The process was killed through its child handle.
+    Killed,
+    /// Waiting for the process failed, after an [`CommandEvent::Error`] event, so it is unknown how
+    /// it terminated.
+    Unknown,
+}
+
+/// Records that the plugin is killing a process for `reason`, unless it already is for another one.
+fn mark_killed(killed: &Mutex<Option<TerminationReason>>, reason: TerminationReason) {
+    killed.lock().unwrap().get_or_insert(reason);
 }
 
 /// A event sent to the command callback.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "payload")]
 #[non_exhaustive]
 pub enum CommandEvent {
     /// Stderr bytes until a newline (\n) or carriage return (\r) is found.
@@ -47,8 +85,24 @@ pub enum CommandEvent {
     Stdout(Vec<u8>),
     /// An error happened waiting for the command to finish or converting the stdout/stderr bytes to an UTF-8 string.
     Error(String),
-    /// Command process terminated.
+    /// Command process terminated, always the last event of the process and sent exactly once.
     Terminated(TerminatedPayload),
+    /// Stderr bytes like [`CommandEvent::Stderr`], sent instead when the command includes timestamps.
+    TimedStderr(TimedLine),
+    /// Stdout bytes like [`CommandEvent::Stdout`], sent instead when the command includes timestamps.
+    TimedStdout(TimedLine),
+    /// Progress of the command between 0 and 1, parsed from an output line by the
+    /// [`Command::progress_regex`] and sent right after the line.
+    Progress(f64),
+}
+
+/// Payload for the [`CommandEvent::TimedStdout`] and [`CommandEvent::TimedStderr`] command events.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimedLine {
+    /// The bytes until a newline (\n) or carriage return (\r) is found.
+    pub line: Vec<u8>,
+    /// When the line was read from the process output, not when the event was sent.
+    pub received_at: SystemTime,
 }
 
 /// The type to spawn commands.
@@ -58,7 +112,231 @@ pub struct Command {
     args: Vec<String>,
     env_clear: bool,
     env: HashMap<String, String>,
+    env_remove: Vec<String>,
     current_dir: Option<PathBuf>,
+    normalize_line_endings: bool,
+    include_timestamps: bool,
+    on_consumer_drop: OnConsumerDrop,
+    policy: ExecutionPolicy,
+    timeout_override: Option<Duration>,
+    max_event_rate: Option<u32>,
+    max_event_buffer: usize,
+    on_event_overflow: OnEventOverflow,
+    output_to: Option<PathBuf>,
+    progress_regex: Option<Regex>,
+    io_priority: Option<IoPriority>,
+    tracking: Option<Tracking>,
+}
+
+/// What happens to a spawned process once the receiver of its events is dropped.
+///
+/// The drop is noticed when the next output line is sent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConsumerDrop {
+    /// Keep reading and discarding the output until the process exits, so that it never blocks
+    /// on a full pipe or receives a `SIGPIPE`.
+    #[default]
+    DrainOutput,
+    /// Kill the process, its remaining output is drained.
+    KillChild,
+}
+
+/// What [`Command::max_event_rate`] does with the output once its buffer is full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnEventOverflow {
+    /// Stop reading the output until buffered events are sent, so that the process blocks on a
+    /// full pipe.
+    #[default]
+    Block,
+    /// Drop the output lines and progress events that do not fit in the buffer. They are never
+    /// sent, but the [`CommandEvent::Error`] and [`CommandEvent::Terminated`] events always are.
+    DropNewest,
+}
+
+/// The default [`Command::max_event_buffer`] size, 1 MiB.
+pub const DEFAULT_MAX_EVENT_BUFFER: usize = 1024 * 1024;
+
+/// The disk I/O priority of a spawned process.
+///
+/// Applied with `ioprio_set` on Linux and `setiopolicy_np` on macOS, where the best-effort levels
+/// are mapped to the coarser I/O policies. Not supported on Windows, where it is ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Only gets disk time when no other process needs it.
+    Idle,
+    /// Best-effort scheduling at the given level, from 0 (highest) to 7 (lowest).
+    BestEffort(u8),
+}
+
+#[cfg(target_os = "linux")]
+impl IoPriority {
+    /// The `ioprio` value, made of the scheduling class and the level within that class.
+    fn ioprio(self) -> libc::c_int {
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        const IOPRIO_CLASS_BE: libc::c_int = 2;
+        const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+        match self {
+            Self::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            Self::BestEffort(level) => {
+                IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT | libc::c_int::from(level.min(7))
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn setiopolicy_np(iotype: libc::c_int, scope: libc::c_int, policy: libc::c_int) -> libc::c_int;
+}
+
+#[cfg(target_os = "macos")]
+impl IoPriority {
+    /// The disk I/O policy of `setiopolicy_np`, from `<sys/resource.h>`.
+    fn iopolicy(self) -> libc::c_int {
+        const IOPOL_IMPORTANT: libc::c_int = 1;
+        const IOPOL_THROTTLE: libc::c_int = 3;
+        const IOPOL_UTILITY: libc::c_int = 4;
+        const IOPOL_STANDARD: libc::c_int = 5;
+        match self {
+            Self::Idle => IOPOL_THROTTLE,
+            // the default level of Linux is 4, and the default policy of macOS is `IOPOL_IMPORTANT`
+            Self::BestEffort(0..=4) => IOPOL_IMPORTANT,
+            Self::BestEffort(5 | 6) => IOPOL_STANDARD,
+            Self::BestEffort(_) => IOPOL_UTILITY,
+        }
+    }
+}
+
+/// Controls how a command is executed: when it times out, how it is retried and what counts as success.
+///
+/// The default policy runs the command once, without a timeout, and only considers a zero exit code
+/// a success.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionPolicy {
+    /// Kill the process if it is still running after this duration.
+    pub timeout: Option<Duration>,
+    /// How many times [`Command::status`] and [`Command::output`] retry an unsuccessful execution.
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each following retry.
+    pub backoff: Duration,
+    /// The exit codes considered a success.
+    pub success_codes: Vec<i32>,
+    /// The Unix signals whose termination is considered a success, e.g. `SIGTERM` for tools
+    /// stopped by it. Ignored on other platforms, and for processes killed on timeout.
+    pub expected_signals: Vec<i32>,
+    /// Maximum number of bytes [`Command::output`] collects from stdout, and from stderr.
+    pub max_output: Option<usize>,
+}
+
+impl Default for ExecutionPolicy {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            retries: 0,
+            backoff: Duration::from_millis(500),
+            success_codes: vec![0],
+            expected_signals: Vec::new(),
+            max_output: None,
+        }
+    }
+}
+
+/// Tracks the children spawned from a single scoped command.
+#[derive(Debug, Default)]
+pub(crate) struct ChildTracker {
+    running: AtomicUsize,
+    children: Mutex<HashMap<u32, KillHandle>>,
+}
+
+/// How long [`ChildTracker::kill_all`] waits for a process to exit after asking it to terminate.
+const KILL_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+impl ChildTracker {
+    /// Returns how many commands are currently accounted as running.
+    pub(crate) fn running(&self) -> usize {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Terminates all the running children, returning how many there were.
+    ///
+    /// On Unix, each child is sent a `SIGTERM` and killed once the [`KILL_GRACE_PERIOD`] elapsed
+    /// if it is still running. Elsewhere, children are killed right away. This does not block.
+    pub(crate) fn kill_all(&self) -> usize {
+        let children: Vec<_> = self.children.lock().unwrap().values().cloned().collect();
+        for (child, killed) in &children {
+            mark_killed(killed, TerminationReason::Killed);
+            #[cfg(unix)]
+            {
+                use shared_child::unix::SharedChildExt;
+                // the signal is not sent once the child was reaped, so its pid cannot be reused
+                let _ = child.send_signal(libc::SIGTERM);
+                let child = child.clone();
+                spawn(move || {
+                    let deadline = Instant::now() + KILL_GRACE_PERIOD;
+                    while matches!(child.try_wait(), Ok(None)) {
+                        if Instant::now() >= deadline {
+                            let _ = child.kill();
+                            return;
+                        }
+                        std::thread::sleep(Duration::from_millis(50));
+                    }
+                });
+            }
+            #[cfg(not(unix))]
+            let _ = child.kill();
+        }
+        children.len()
+    }
+}
+
+/// The tracker of the scoped command a [`Command`] was prepared from, see [`Command::tracked`].
+#[derive(Debug, Clone)]
+pub(crate) struct Tracking {
+    /// The name of the scoped command.
+    pub(crate) name: String,
+    /// The tracker of the scoped command.
+    pub(crate) tracker: Arc<ChildTracker>,
+    /// If only one instance of the scoped command can run at a time.
+    pub(crate) singleton: bool,
+}
+
+impl Tracking {
+    /// Accounts one more instance of the command as running, unless it is a singleton that is
+    /// already running.
+    fn acquire(&self) -> crate::Result<RunningGuard> {
+        if self.singleton {
+            RunningGuard::exclusive(self.tracker.clone())
+                .ok_or_else(|| crate::scope::Error::AlreadyRunning(self.name.clone()).into())
+        } else {
+            Ok(RunningGuard::new(self.tracker.clone()))
+        }
+    }
+}
+
+/// Keeps a scoped command accounted as running on its [`ChildTracker`] until it is dropped.
+#[derive(Debug)]
+struct RunningGuard(Arc<ChildTracker>);
+
+impl RunningGuard {
+    fn new(tracker: Arc<ChildTracker>) -> Self {
+        tracker.running.fetch_add(1, Ordering::SeqCst);
+        Self(tracker)
+    }
+
+    /// Accounts a command as running only if no other command is running on the tracker.
+    fn exclusive(tracker: Arc<ChildTracker>) -> Option<Self> {
+        tracker
+            .running
+            .compare_exchange(0, 1, Ordering::SeqCst, Ordering::SeqCst)
+            .ok()
+            .map(|_| Self(tracker))
+    }
+}
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.running.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 /// Spawned child process.
@@ -66,6 +344,7 @@ pub struct Command {
 pub struct CommandChild {
     inner: Arc<SharedChild>,
     stdin_writer: PipeWriter,
+    killed: Arc<Mutex<Option<TerminationReason>>>,
 }
 
 impl CommandChild {
@@ -77,6 +356,67 @@ impl CommandChild {
 
     /// Sends a kill signal to the child.
     pub fn kill(self) -> crate::Result<()> {
+        mark_killed(&self.killed, TerminationReason::Killed);
+        self.inner.kill()?;
+        Ok(())
+    }
+
+    /// Returns the process pid.
+    pub fn pid(&self) -> u32 {
+        self.inner.id()
+    }
+}
+
+/// Spawned child process with an open stdin, for interactive sessions.
+#[derive(Debug)]
+pub struct InteractiveChild {
+    inner: Arc<SharedChild>,
+    stdin_writer: Mutex<Option<PipeWriter>>,
+    rx: Receiver<CommandEvent>,
+    killed: Arc<Mutex<Option<TerminationReason>>>,
+}
+
+impl InteractiveChild {
+    pub(crate) fn new(rx: Receiver<CommandEvent>, child: CommandChild) -> Self {
+        Self {
+            inner: child.inner,
+            stdin_writer: Mutex::new(Some(child.stdin_writer)),
+            rx,
+            killed: child.killed,
+        }
+    }
+
+    /// Writes to process stdin.
+    ///
+    /// Fails with a broken pipe error once the stdin was closed.
+    pub fn write_stdin(&self, buf: &[u8]) -> crate::Result<()> {
+        match self.stdin_writer.lock().unwrap().as_mut() {
+            Some(writer) => writer.write_all(buf)?,
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "the process stdin was closed",
+                )
+                .into())
+            }
+        }
+        Ok(())
+    }
+
+    /// Closes the process stdin, signaling the end of its input.
+    pub fn close_stdin(&self) {
+        self.stdin_writer.lock().unwrap().take();
+    }
+
+    /// Receives the next event of the process, or `None` once the process terminated
+    /// and all of its events were received.
+    pub async fn recv(&mut self) -> Option<CommandEvent> {
+        self.rx.recv().await
+    }
+
+    /// Sends a kill signal to the child.
+    pub fn kill(&self) -> crate::Result<()> {
+        mark_killed(&self.killed, TerminationReason::Killed);
         self.inner.kill()?;
         Ok(())
     }
@@ -91,6 +431,8 @@ impl CommandChild {
 #[derive(Debug)]
 pub struct ExitStatus {
     code: Option<i32>,
+    signal: Option<i32>,
+    success: bool,
 }
 
 impl ExitStatus {
@@ -99,9 +441,76 @@ impl ExitStatus {
         self.code
     }
 
-    /// Returns true if exit status is zero. Signal termination is not considered a success, and success is defined as a zero exit status.
+    /// Returns the signal that terminated the process on Unix, if any.
+    pub fn signal(&self) -> Option<i32> {
+        self.signal
+    }
+
+    /// Returns true if the exit code is one of the [`ExecutionPolicy::success_codes`], which default to zero.
+    /// Signal termination is only considered a success for the [`ExecutionPolicy::expected_signals`].
     pub fn success(&self) -> bool {
-        self.code == Some(0)
+        self.success
+    }
+}
+
+/// Parts of environment variable names whose values are masked in [`SpawnedInfo`].
+const SECRET_ENV_MARKERS: &[&str] = &["SECRET", "TOKEN", "PASSWORD", "PASSWD", "CREDENTIAL", "KEY"];
+
+/// Whether the environment variable looks like it holds a secret value.
+pub(crate) fn is_secret_env(name: &str) -> bool {
+    let name = name.to_uppercase();
+    SECRET_ENV_MARKERS
+        .iter()
+        .any(|marker| name.contains(marker))
+}
+
+/// The exact invocation of a spawned command, for reproducing it.
+///
+/// The values of environment variables that look like secrets (e.g. `API_TOKEN`) are masked.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpawnedInfo {
+    /// The program that was executed.
+    pub program: PathBuf,
+    /// The arguments the program was called with.
+    pub args: Vec<String>,
+    /// The working directory, if not inherited from the parent process.
+    pub cwd: Option<PathBuf>,
+    /// The environment variables set on top of the inherited (or cleared) environment.
+    pub env_diff: Vec<(String, String)>,
+    /// Whether the inherited environment was cleared.
+    pub env_cleared: bool,
+}
+
+impl fmt::Display for SpawnedInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(cwd) = &self.cwd {
+            write!(f, "cd {} && ", quote_arg(&cwd.to_string_lossy()))?;
+        }
+        if self.env_cleared {
+            f.write_str("env -i ")?;
+        }
+        for (name, value) in &self.env_diff {
+            write!(f, "{name}={} ", quote_arg(value))?;
+        }
+        f.write_str(&quote_arg(&self.program.to_string_lossy()))?;
+        for arg in &self.args {
+            write!(f, " {}", quote_arg(arg))?;
+        }
+        Ok(())
+    }
+}
+
+/// Quotes the argument for a POSIX shell, if needed.
+fn quote_arg(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
     }
 }
 
@@ -114,6 +523,8 @@ pub struct Output {
     pub stdout: Vec<u8>,
     /// The data that the process wrote to stderr.
     pub stderr: Vec<u8>,
+    /// The invocation that produced this output.
+    pub spawned: SpawnedInfo,
 }
 
 fn relative_command_path(command: String) -> crate::Result<String> {
@@ -126,23 +537,11 @@ fn relative_command_path(command: String) -> crate::Result<String> {
     }
 }
 
+/// The converted command is no longer tracked by the scope it was prepared from, so its process
+/// does not count as running.
 impl From<Command> for StdCommand {
     fn from(cmd: Command) -> StdCommand {
-        let mut command = StdCommand::new(cmd.program);
-        command.args(cmd.args);
-        command.stdout(Stdio::piped());
-        command.stdin(Stdio::piped());
-        command.stderr(Stdio::piped());
-        if cmd.env_clear {
-            command.env_clear();
-        }
-        command.envs(cmd.env);
-        if let Some(current_dir) = cmd.current_dir {
-            command.current_dir(current_dir);
-        }
-        #[cfg(windows)]
-        command.creation_flags(CREATE_NO_WINDOW);
-        command
+        cmd.std_command()
     }
 }
 
@@ -153,7 +552,20 @@ impl Command {
             args: Default::default(),
             env_clear: false,
             env: Default::default(),
+            env_remove: Default::default(),
             current_dir: None,
+            normalize_line_endings: false,
+            include_timestamps: false,
+            on_consumer_drop: OnConsumerDrop::default(),
+            policy: Default::default(),
+            timeout_override: None,
+            max_event_rate: None,
+            max_event_buffer: DEFAULT_MAX_EVENT_BUFFER,
+            on_event_overflow: OnEventOverflow::default(),
+            output_to: None,
+            progress_regex: None,
+            io_priority: None,
+            tracking: None,
         }
     }
 
@@ -161,6 +573,158 @@ impl Command {
         Ok(Self::new(relative_command_path(program.into())?))
     }
 
+    /// Converts `\r\n` and lone `\r` line endings in the process output to `\n`.
+    ///
+    /// Lines ending with a carriage return are delivered without it, even when the `\r\n` pair is
+    /// split across two reads of the output pipe.
+    #[must_use]
+    pub fn normalize_line_endings(mut self, normalize: bool) -> Self {
+        self.normalize_line_endings = normalize;
+        self
+    }
+
+    /// Sends the output lines of the spawned command as [`CommandEvent::TimedStdout`] and
+    /// [`CommandEvent::TimedStderr`], carrying the time each line was read.
+    ///
+    /// This is only available to Rust consumers: the webview API cannot enable it, and the events
+    /// it receives carry the lines without their timestamps.
+    #[must_use]
+    pub fn include_timestamps(mut self, include: bool) -> Self {
+        self.include_timestamps = include;
+        self
+    }
+
+    /// Sets what happens to the spawned process once the receiver of its events is dropped.
+    #[must_use]
+    pub fn on_consumer_drop(mut self, on_drop: OnConsumerDrop) -> Self {
+        self.on_consumer_drop = on_drop;
+        self
+    }
+
+    /// Sets the execution policy of the command.
+    #[must_use]
+    pub fn policy(mut self, policy: ExecutionPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Overrides the timeout of the execution policy for this invocation, `None` uses the policy one.
+    ///
+    /// The override is kept when the policy is replaced with [`Self::policy`].
+    #[must_use]
+    pub fn timeout_override(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout_override = timeout;
+        self
+    }
+
+    /// Limits the events sent by [`Self::spawn`] to `rate` per second, `None` for no limit.
+    ///
+    /// Output lines exceeding the rate are buffered, and consecutive lines of the same stream are
+    /// then sent as a single event joined by `\n`. The buffer is bounded by
+    /// [`Self::max_event_buffer`].
+    #[must_use]
+    pub fn max_event_rate(mut self, rate: Option<u32>) -> Self {
+        self.max_event_rate = rate.filter(|rate| *rate > 0);
+        self
+    }
+
+    /// Bounds the output buffered by [`Self::max_event_rate`] to about `bytes` bytes,
+    /// [`DEFAULT_MAX_EVENT_BUFFER`] by default.
+    ///
+    /// Once the buffer is full, the output is either no longer read until events are sent, or
+    /// dropped, see [`OnEventOverflow`].
+    #[must_use]
+    pub fn max_event_buffer(mut self, bytes: usize, on_overflow: OnEventOverflow) -> Self {
+        self.max_event_buffer = bytes;
+        self.on_event_overflow = on_overflow;
+        self
+    }
+
+    /// Also writes the output lines received by [`Self::spawn`] to a file, truncating it.
+    ///
+    /// Stdout and stderr lines are both written, each followed by `\n`. The file is written by a
+    /// buffered writer on another thread, so a slow disk does not delay the events, and it is
+    /// complete once the receiver returns `None`. Writing stops at the first error.
+    ///
+    /// The file keeps being written if the receiver is dropped, unless the command kills the
+    /// process then, see [`OnConsumerDrop::KillChild`].
+    #[must_use]
+    pub fn output_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output_to = Some(path.into());
+        self
+    }
+
+    /// Parses the output lines received by [`Self::spawn`] with `regex`, sending a
+    /// [`CommandEvent::Progress`] event for each matching line.
+    ///
+    /// The progress is read from a `percent` named capture group, or from `current` and `total`
+    /// ones, e.g. `(?P<percent>\d+)%` or `(?P<current>\d+)/(?P<total>\d+)`, and clamped between 0
+    /// and 1. The lines are still sent as output events, whether they match or not.
+    #[must_use]
+    pub fn progress_regex(mut self, regex: Regex) -> Self {
+        self.progress_regex = Some(regex);
+        self
+    }
+
+    /// Sets the disk I/O priority of the child process, see [`IoPriority`].
+    #[must_use]
+    pub fn io_priority(mut self, priority: IoPriority) -> Self {
+        self.io_priority.replace(priority);
+        self
+    }
+
+    /// Returns the invocation this command spawns, with secret-looking environment values masked.
+    pub fn spawned_info(&self) -> SpawnedInfo {
+        let mut env_diff: Vec<_> = self
+            .env
+            .iter()
+            .map(|(name, value)| {
+                let value = if is_secret_env(name) {
+                    "***".to_string()
+                } else {
+                    value.clone()
+                };
+                (name.clone(), value)
+            })
+            .collect();
+        env_diff.sort();
+        SpawnedInfo {
+            program: PathBuf::from(&self.program),
+            args: self.args.clone(),
+            cwd: self.current_dir.clone(),
+            env_diff,
+            env_cleared: self.env_clear,
+        }
+    }
+
+    /// The environment variables the spawned process receives.
+    pub(crate) fn resolved_env(&self) -> HashMap<String, String> {
+        let mut env: HashMap<String, String> = if self.env_clear {
+            HashMap::new()
+        } else {
+            std::env::vars_os()
+                .map(|(name, value)| {
+                    (
+                        name.to_string_lossy().into_owned(),
+                        value.to_string_lossy().into_owned(),
+                    )
+                })
+                .collect()
+        };
+        env.extend(self.env.clone());
+        for key in &self.env_remove {
+            env.remove(key);
+        }
+        env
+    }
+
+    /// Accounts each spawned child of the command as running on the tracker until it exits.
+    #[must_use]
+    pub(crate) fn tracked(mut self, tracking: Tracking) -> Self {
+        self.tracking.replace(tracking);
+        self
+    }
+
     /// Appends arguments to the command.
     #[must_use]
     pub fn args<I, S>(mut self, args: I) -> Self
@@ -188,6 +752,15 @@ impl Command {
         self
     }
 
+    /// Removes an environment variable inherited by the child process.
+    ///
+    /// The variable is removed even if it is also set with [`Self::envs`].
+    #[must_use]
+    pub fn env_remove(mut self, key: impl Into<String>) -> Self {
+        self.env_remove.push(key.into());
+        self
+    }
+
     /// Sets the working directory for the child process.
     #[must_use]
     pub fn current_dir(mut self, current_dir: PathBuf) -> Self {
@@ -225,8 +798,81 @@ impl Command {
     ///     Ok(())
     /// });
     /// ```
-    pub fn spawn(self) -> crate::Result<(Receiver<CommandEvent>, CommandChild)> {
-        let mut command: StdCommand = self.into();
+    pub fn spawn(mut self) -> crate::Result<(Receiver<CommandEvent>, CommandChild)> {
+        let file = self.output_to.as_ref().map(File::create).transpose()?;
+        let (mut rx, child) = self.spawn_tracked()?;
+        if let Some(file) = file {
+            let kill_on_drop = (self.on_consumer_drop == OnConsumerDrop::KillChild)
+                .then(|| (child.inner.clone(), child.killed.clone()));
+            rx = tee(rx, file, kill_on_drop);
+        }
+        if let Some(regex) = self.progress_regex.take() {
+            rx = parse_progress(rx, regex);
+        }
+        match self.max_event_rate {
+            Some(rate) => {
+                let buffer = EventBuffer::new(self.max_event_buffer, self.on_event_overflow);
+                Ok((throttle(rx, rate, buffer), child))
+            }
+            None => Ok((rx, child)),
+        }
+    }
+
+    fn std_command(&self) -> StdCommand {
+        let mut command = StdCommand::new(&self.program);
+        command.args(&self.args);
+        command.stdout(Stdio::piped());
+        command.stdin(Stdio::piped());
+        command.stderr(Stdio::piped());
+        if self.env_clear {
+            command.env_clear();
+        }
+        command.envs(&self.env);
+        for key in &self.env_remove {
+            command.env_remove(key);
+        }
+        if let Some(current_dir) = &self.current_dir {
+            command.current_dir(current_dir);
+        }
+        #[cfg(windows)]
+        command.creation_flags(CREATE_NO_WINDOW);
+        #[cfg(target_os = "linux")]
+        if let Some(priority) = self.io_priority {
+            const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+            let ioprio = priority.ioprio();
+            // SAFETY: only a raw syscall runs between fork and exec, which is async-signal-safe
+            unsafe {
+                command.pre_exec(move || {
+                    // the priority is best-effort, the command still runs if the kernel refuses it
+                    libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, ioprio);
+                    Ok(())
+                });
+            }
+        }
+        #[cfg(target_os = "macos")]
+        if let Some(priority) = self.io_priority {
+            const IOPOL_TYPE_DISK: libc::c_int = 0;
+            const IOPOL_SCOPE_PROCESS: libc::c_int = 0;
+            let policy = priority.iopolicy();
+            // SAFETY: only a system call wrapper runs between fork and exec, which is async-signal-safe
+            unsafe {
+                command.pre_exec(move || {
+                    // the policy is kept across exec, and is best-effort like on Linux
+                    setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_PROCESS, policy);
+                    Ok(())
+                });
+            }
+        }
+        command
+    }
+
+    /// Spawns the command, keeping it accounted as running on its tracker until the child exits.
+    fn spawn_tracked(&self) -> crate::Result<(Receiver<CommandEvent>, CommandChild)> {
+        let running = self.tracking.as_ref().map(Tracking::acquire).transpose()?;
+        let spawned = self.spawned_info();
+        let normalize_line_endings = self.normalize_line_endings;
+        let include_timestamps = self.include_timestamps;
+        let mut command = self.std_command();
         let (stdout_reader, stdout_writer) = pipe()?;
         let (stderr_reader, stderr_writer) = pipe()?;
         let (stdin_reader, stdin_writer) = pipe()?;
@@ -234,49 +880,110 @@ impl Command {
         command.stderr(stderr_writer);
         command.stdin(stdin_reader);
 
-        let shared_child = SharedChild::spawn(&mut command)?;
+        let shared_child = SharedChild::spawn(&mut command)
+            .map_err(|source| crate::Error::Spawn { spawned, source })?;
+        let started = Instant::now();
         let child = Arc::new(shared_child);
         let child_ = child.clone();
         let guard = Arc::new(RwLock::new(()));
+        let killed = Arc::new(Mutex::new(None));
+        let killed_ = killed.clone();
+
+        let exited = self
+            .timeout_override
+            .or(self.policy.timeout)
+            .map(|timeout| {
+                let (exited_tx, exited_rx) = mpsc::channel::<()>();
+                let child = child.clone();
+                let killed = killed.clone();
+                spawn(move || {
+                    if let Err(RecvTimeoutError::Timeout) = exited_rx.recv_timeout(timeout) {
+                        mark_killed(&killed, TerminationReason::Timeout);
+                        let _ = child.kill();
+                    }
+                });
+                exited_tx
+            });
 
         //TODO commands().lock().unwrap().insert(child.id(), child.clone());
 
+        let tracked = running.as_ref().map(|guard| {
+            let tracker = guard.0.clone();
+            tracker
+                .children
+                .lock()
+                .unwrap()
+                .insert(child.id(), (child.clone(), killed.clone()));
+            tracker
+        });
+
         let (tx, rx) = channel(1);
+        let kill_on_drop = || {
+            (self.on_consumer_drop == OnConsumerDrop::KillChild)
+                .then(|| (child.clone(), killed.clone()))
+        };
 
         spawn_pipe_reader(
             tx.clone(),
             guard.clone(),
             stdout_reader,
+            normalize_line_endings,
             CommandEvent::Stdout,
+            include_timestamps.then_some(CommandEvent::TimedStdout),
+            kill_on_drop(),
         );
         spawn_pipe_reader(
             tx.clone(),
             guard.clone(),
             stderr_reader,
+            normalize_line_endings,
             CommandEvent::Stderr,
+            include_timestamps.then_some(CommandEvent::TimedStderr),
+            kill_on_drop(),
         );
 
         spawn(move || {
-            let _ = match child_.wait() {
+            let result = child_.wait();
+            drop(exited);
+            if let Some(tracker) = tracked {
+                tracker.children.lock().unwrap().remove(&child_.id());
+            }
+            drop(running);
+            let duration = started.elapsed();
+            let killed = *killed_.lock().unwrap();
+            let _l = guard.write().unwrap();
+            //TODO commands().lock().unwrap().remove(&child_.id());
+            let payload = match result {
                 Ok(status) => {
-                    let _l = guard.write().unwrap();
-                    //TODO commands().lock().unwrap().remove(&child_.id());
-                    block_on_task(async move {
-                        tx.send(CommandEvent::Terminated(TerminatedPayload {
-                            code: status.code(),
-                            #[cfg(windows)]
-                            signal: None,
-                            #[cfg(unix)]
-                            signal: status.signal(),
-                        }))
-                        .await
-                    })
+                    #[cfg(windows)]
+                    let signal = None;
+                    #[cfg(unix)]
+                    let signal = status.signal();
+                    // on Windows a killed process has an exit code, so the kill request takes precedence
+                    let reason = killed.unwrap_or(match status.code() {
+                        Some(_) => TerminationReason::Exited,
+                        None => TerminationReason::Signaled,
+                    });
+                    TerminatedPayload {
+                        code: status.code(),
+                        signal,
+                        reason,
+                        duration,
+                    }
                 }
                 Err(e) => {
-                    let _l = guard.write().unwrap();
-                    block_on_task(async move { tx.send(CommandEvent::Error(e.to_string())).await })
+                    let error = e.to_string();
+                    let tx = tx.clone();
+                    let _ = block_on_task(async move { tx.send(CommandEvent::Error(error)).await });
+                    TerminatedPayload {
+                        code: None,
+                        signal: None,
+                        reason: killed.unwrap_or(TerminationReason::Unknown),
+                        duration,
+                    }
                 }
             };
+            let _ = block_on_task(async move { tx.send(CommandEvent::Terminated(payload)).await });
         });
 
         Ok((
@@ -284,6 +991,7 @@ impl Command {
             CommandChild {
                 inner: child,
                 stdin_writer,
+                killed,
             },
         ))
     }
@@ -302,15 +1010,21 @@ impl Command {
     ///   });
     /// ```
     pub async fn status(self) -> crate::Result<ExitStatus> {
-        let (mut rx, _child) = self.spawn()?;
-        let mut code = None;
-        #[allow(clippy::collapsible_match)]
-        while let Some(event) = rx.recv().await {
-            if let CommandEvent::Terminated(payload) = event {
-                code = payload.code;
+        let mut attempt = 0;
+        loop {
+            let (mut rx, _child) = self.spawn_tracked()?;
+            let mut terminated = None;
+            #[allow(clippy::collapsible_match)]
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Terminated(payload) = event {
+                    terminated = Some(payload);
+                }
+            }
+            let status = self.exit_status(terminated);
+            if status.success() || !self.retry(&mut attempt).await {
+                return Ok(status);
             }
         }
-        Ok(ExitStatus { code })
     }
 
     /// Executes the command as a child process, waiting for it to finish and collecting all of its output.
@@ -329,45 +1043,105 @@ impl Command {
     ///   });
     /// ```
     pub async fn output(self) -> crate::Result<Output> {
-        let (mut rx, _child) = self.spawn()?;
+        let mut attempt = 0;
+        loop {
+            let output = self.output_once().await?;
+            if output.status.success() || !self.retry(&mut attempt).await {
+                return Ok(output);
+            }
+        }
+    }
 
-        let mut code = None;
+    async fn output_once(&self) -> crate::Result<Output> {
+        let spawned = self.spawned_info();
+        let (mut rx, _child) = self.spawn_tracked()?;
+
+        let mut terminated = None;
         let mut stdout = Vec::new();
         let mut stderr = Vec::new();
 
         while let Some(event) = rx.recv().await {
             match event {
                 CommandEvent::Terminated(payload) => {
-                    code = payload.code;
+                    terminated = Some(payload);
                 }
-                CommandEvent::Stdout(line) => {
-                    stdout.extend(line);
-                    stdout.push(NEWLINE_BYTE);
+                CommandEvent::Stdout(line) | CommandEvent::TimedStdout(TimedLine { line, .. }) => {
+                    collect_line(&mut stdout, line, self.policy.max_output);
                 }
-                CommandEvent::Stderr(line) => {
-                    stderr.extend(line);
-                    stderr.push(NEWLINE_BYTE);
+                CommandEvent::Stderr(line) | CommandEvent::TimedStderr(TimedLine { line, .. }) => {
+                    collect_line(&mut stderr, line, self.policy.max_output);
                 }
-                CommandEvent::Error(_) => {}
+                CommandEvent::Error(_) | CommandEvent::Progress(_) => {}
             }
         }
         Ok(Output {
-            status: ExitStatus { code },
+            status: self.exit_status(terminated),
             stdout,
             stderr,
+            spawned,
         })
     }
+
+    fn exit_status(&self, terminated: Option<TerminatedPayload>) -> ExitStatus {
+        let (code, signal, reason) = match terminated {
+            Some(payload) => (payload.code, payload.signal, Some(payload.reason)),
+            None => (None, None, None),
+        };
+        let success = match (code, signal) {
+            (Some(code), _) => self.policy.success_codes.contains(&code),
+            (None, Some(signal)) => {
+                reason == Some(TerminationReason::Signaled)
+                    && self.policy.expected_signals.contains(&signal)
+            }
+            (None, None) => false,
+        };
+        ExitStatus {
+            code,
+            signal,
+            success,
+        }
+    }
+
+    /// Waits for the backoff delay of the next retry, returning `false` if there are no retries left.
+    async fn retry(&self, attempt: &mut u32) -> bool {
+        if *attempt >= self.policy.retries {
+            return false;
+        }
+        let delay = self
+            .policy
+            .backoff
+            .saturating_mul(2u32.saturating_pow(*attempt));
+        *attempt += 1;
+        tokio::time::sleep(delay).await;
+        true
+    }
 }
 
+/// Appends an output line to `buf`, keeping at most `max_output` bytes.
+fn collect_line(buf: &mut Vec<u8>, line: Vec<u8>, max_output: Option<usize>) {
+    buf.extend(line);
+    buf.push(NEWLINE_BYTE);
+    if let Some(max_output) = max_output {
+        buf.truncate(max_output);
+    }
+}
+
+/// A process to kill, with its kill reason.
+type KillHandle = (Arc<SharedChild>, Arc<Mutex<Option<TerminationReason>>>);
+
 fn spawn_pipe_reader<F: Fn(Vec<u8>) -> CommandEvent + Send + Copy + 'static>(
     tx: Sender<CommandEvent>,
     guard: Arc<RwLock<()>>,
     pipe_reader: PipeReader,
+    normalize_line_endings: bool,
     wrapper: F,
+    timed_wrapper: Option<fn(TimedLine) -> CommandEvent>,
+    mut kill_on_drop: Option<KillHandle>,
 ) {
     spawn(move || {
         let _lock = guard.read().unwrap();
         let mut reader = BufReader::new(pipe_reader);
+        let mut line_endings = LineEndings::default();
 
         loop {
             let mut buf = Vec::new();
@@ -376,8 +1150,26 @@ fn spawn_pipe_reader<F: Fn(Vec<u8>) -> CommandEvent + Send + Copy + 'static>(
                     if n == 0 {
                         break;
                     }
-                    let tx_ = tx.clone();
-                    let _ = block_on_task(async move { tx_.send(wrapper(buf)).await });
+                    let received_at = SystemTime::now();
+                    let lines = if normalize_line_endings {
+                        line_endings.normalize(buf, n)
+                    } else {
+                        vec![buf]
+                    };
+                    for line in lines {
+                        let event = match timed_wrapper {
+                            Some(timed_wrapper) => timed_wrapper(TimedLine { line, received_at }),
+                            None => wrapper(line),
+                        };
+                        let tx_ = tx.clone();
+                        if block_on_task(async move { tx_.send(event).await }).is_err() {
+                            // the receiver was dropped, the output is drained from now on
+                            if let Some((child, killed)) = kill_on_drop.take() {
+                                mark_killed(&killed, TerminationReason::Killed);
+                                let _ = child.kill();
+                            }
+                        }
+                    }
                 }
                 Err(e) => {
                     let tx_ = tx.clone();
@@ -391,6 +1183,276 @@ fn spawn_pipe_reader<F: Fn(Vec<u8>) -> CommandEvent + Send + Copy + 'static>(
     });
 }
 
+/// Forwards the events of `rx`, writing their output lines to `file` on another thread.
+///
+/// The reader threads only notice a dropped receiver by the events they send, which this
+/// receives, so the process to kill then is passed here instead.
+fn tee(
+    mut rx: Receiver<CommandEvent>,
+    file: File,
+    mut kill_on_drop: Option<KillHandle>,
+) -> Receiver<CommandEvent> {
+    let (tx, teed) = channel(1);
+    let (lines_tx, lines_rx) = mpsc::channel::<Vec<u8>>();
+    let writer = spawn(move || {
+        let mut writer = BufWriter::new(file);
+        for line in lines_rx {
+            if writer
+                .write_all(&line)
+                .and_then(|_| writer.write_all(&[NEWLINE_BYTE]))
+                .is_err()
+            {
+                return;
+            }
+        }
+        let _ = writer.flush();
+    });
+    spawn(move || {
+        // the file keeps being written if the receiver is dropped, with the drained output
+        let mut delivering = true;
+        while let Some(event) = rx.blocking_recv() {
+            if let CommandEvent::Stdout(line)
+            | CommandEvent::Stderr(line)
+            | CommandEvent::TimedStdout(TimedLine { line, .. })
+            | CommandEvent::TimedStderr(TimedLine { line, .. }) = &event
+            {
+                let _ = lines_tx.send(line.clone());
+            }
+            delivering = delivering && block_on_task(tx.send(event)).is_ok();
+            if !delivering {
+                if let Some((child, killed)) = kill_on_drop.take() {
+                    mark_killed(&killed, TerminationReason::Killed);
+                    let _ = child.kill();
+                }
+            }
+        }
+        // the receiver only closes once the file is complete
+        drop(lines_tx);
+        let _ = writer.join();
+        drop(tx);
+    });
+    teed
+}
+
+/// Forwards the events of `rx`, following each output line matching `regex` by its progress.
+fn parse_progress(mut rx: Receiver<CommandEvent>, regex: Regex) -> Receiver<CommandEvent> {
+    let (tx, parsed) = channel(1);
+    spawn(move || {
+        while let Some(event) = rx.blocking_recv() {
+            let progress = match &event {
+                CommandEvent::Stdout(line)
+                | CommandEvent::Stderr(line)
+                | CommandEvent::TimedStdout(TimedLine { line, .. })
+                | CommandEvent::TimedStderr(TimedLine { line, .. }) => {
+                    progress_of(&regex, &String::from_utf8_lossy(line))
+                }
+                _ => None,
+            };
+            if block_on_task(tx.send(event)).is_err() {
+                break;
+            }
+            if let Some(progress) = progress {
+                if block_on_task(tx.send(CommandEvent::Progress(progress))).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    parsed
+}
+
+/// Reads the progress of a line from the `percent`, or `current` and `total` captures of `regex`.
+fn progress_of(regex: &Regex, line: &str) -> Option<f64> {
+    let captures = regex.captures(line)?;
+    let number = |name| captures.name(name)?.as_str().parse::<f64>().ok();
+    let progress = match number("percent") {
+        Some(percent) => percent / 100.0,
+        None => {
+            let total = number("total").filter(|total| *total > 0.0)?;
+            number("current")? / total
+        }
+    };
+    Some(progress.clamp(0.0, 1.0)).filter(|progress| !progress.is_nan())
+}
+
+/// Forwards the events of `rx` at most `rate` times per second, batching the lines that wait in
+/// `pending`.
+fn throttle(
+    mut rx: Receiver<CommandEvent>,
+    rate: u32,
+    mut pending: EventBuffer,
+) -> Receiver<CommandEvent> {
+    let (tx, throttled) = channel(1);
+    spawn(move || {
+        let mut bucket = TokenBucket::new(rate);
+        loop {
+            if pending.events.is_empty() {
+                match rx.blocking_recv() {
+                    Some(event) => pending.push(event),
+                    None => break,
+                }
+            }
+            bucket.take();
+            while !pending.is_full() {
+                match rx.try_recv() {
+                    Ok(event) => pending.push(event),
+                    Err(_) => break,
+                }
+            }
+            if let Some(event) = pending.pop_front() {
+                if block_on_task(tx.send(event)).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    throttled
+}
+
+/// The events waiting to be sent by [`throttle`], bounded to about `max_bytes` bytes.
+struct EventBuffer {
+    events: VecDeque<CommandEvent>,
+    bytes: usize,
+    max_bytes: usize,
+    on_overflow: OnEventOverflow,
+}
+
+impl EventBuffer {
+    fn new(max_bytes: usize, on_overflow: OnEventOverflow) -> Self {
+        Self {
+            events: VecDeque::new(),
+            bytes: 0,
+            max_bytes,
+            on_overflow,
+        }
+    }
+
+    /// Whether no more events should be read until one is sent.
+    fn is_full(&self) -> bool {
+        self.on_overflow == OnEventOverflow::Block && self.bytes >= self.max_bytes
+    }
+
+    /// Queues an event, dropping it if it does not fit and the buffer drops the overflow.
+    fn push(&mut self, event: CommandEvent) {
+        let droppable = !matches!(event, CommandEvent::Error(_) | CommandEvent::Terminated(_));
+        if self.on_overflow == OnEventOverflow::DropNewest
+            && droppable
+            && self.bytes + event_size(&event) > self.max_bytes
+        {
+            return;
+        }
+        self.bytes += push_batched(&mut self.events, event);
+    }
+
+    fn pop_front(&mut self) -> Option<CommandEvent> {
+        let event = self.events.pop_front()?;
+        self.bytes -= event_size(&event);
+        Some(event)
+    }
+}
+
+/// The size accounted for an event in an [`EventBuffer`]: the bytes of a line, or the size of the
+/// event itself otherwise.
+fn event_size(event: &CommandEvent) -> usize {
+    match event {
+        CommandEvent::Stdout(line)
+        | CommandEvent::Stderr(line)
+        | CommandEvent::TimedStdout(TimedLine { line, .. })
+        | CommandEvent::TimedStderr(TimedLine { line, .. }) => line.len(),
+        _ => std::mem::size_of::<CommandEvent>(),
+    }
+}
+
+/// Queues an event, appending it to the last queued one if both are lines of the same stream.
+///
+/// Returns by how much the [`event_size`] of the queued events grew.
+fn push_batched(pending: &mut VecDeque<CommandEvent>, event: CommandEvent) -> usize {
+    let (last, line) = match (pending.back_mut(), &event) {
+        (Some(CommandEvent::Stdout(last)), CommandEvent::Stdout(line))
+        | (Some(CommandEvent::Stderr(last)), CommandEvent::Stderr(line))
+        | (
+            Some(CommandEvent::TimedStdout(TimedLine { line: last, .. })),
+            CommandEvent::TimedStdout(TimedLine { line, .. }),
+        )
+        | (
+            Some(CommandEvent::TimedStderr(TimedLine { line: last, .. })),
+            CommandEvent::TimedStderr(TimedLine { line, .. }),
+        ) => (last, line),
+        _ => {
+            let size = event_size(&event);
+            pending.push_back(event);
+            return size;
+        }
+    };
+    last.push(NEWLINE_BYTE);
+    last.extend_from_slice(line);
+    line.len() + 1
+}
+
+/// A token bucket allowing `rate` events per second, with bursts of up to `rate` events.
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate: rate.into(),
+            tokens: rate.into(),
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes a token, sleeping until one is available.
+    fn take(&mut self) {
+        self.refill();
+        if self.tokens < 1.0 {
+            std::thread::sleep(Duration::from_secs_f64((1.0 - self.tokens) / self.rate));
+            self.refill();
+        }
+        self.tokens -= 1.0;
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.refilled_at = now;
+    }
+}
+
+/// Normalizes the line endings of the lines read by [`tauri::utils::io::read_line`].
+///
+/// `read_line` stops at a `\n`, or at a `\r` if the buffered data has no `\n`, and strips the `\n`.
+/// A `\r\n` pair can therefore show up as a line ending with `\r` followed by an empty line.
+#[derive(Debug, Default)]
+struct LineEndings {
+    /// Whether the previous line ended with a lone `\r`.
+    pending_cr: bool,
+}
+
+impl LineEndings {
+    /// Splits a line read by `read_line`, which consumed `consumed` bytes, into lines without
+    /// carriage returns.
+    fn normalize(&mut self, mut line: Vec<u8>, consumed: usize) -> Vec<Vec<u8>> {
+        let newline = consumed > line.len();
+        let pending_cr = std::mem::take(&mut self.pending_cr);
+        if pending_cr && newline && line.is_empty() {
+            // the `\n` of a `\r\n` pair split across two reads
+            return Vec::new();
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+            self.pending_cr = !newline;
+        }
+        line.split(|byte| *byte == b'\r')
+            .map(<[u8]>::to_vec)
+            .collect()
+    }
+}
+
 // tests for the commands functions.
 #[cfg(test)]
 mod tests {
@@ -418,6 +1480,208 @@ mod tests {
         });
     }
 
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_timestamps() {
+        let cmd = Command::new("cat")
+            .args(["test/test.txt"])
+            .include_timestamps(true);
+        let spawned_at = SystemTime::now();
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            let mut timed = false;
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Stdout(_) => panic!("expected a timed stdout line"),
+                    CommandEvent::TimedStdout(TimedLine { line, received_at }) => {
+                        assert_eq!(String::from_utf8(line).unwrap(), "This is a test doc!");
+                        assert!(received_at >= spawned_at);
+                        timed = true;
+                    }
+                    _ => {}
+                }
+            }
+            assert!(timed);
+        });
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_max_event_rate() {
+        let cmd = Command::new("seq")
+            .args(["1", "200"])
+            .max_event_rate(Some(5));
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        tauri::async_runtime::block_on(async move {
+            let mut events = 0;
+            let mut stdout = Vec::new();
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(line) = event {
+                    events += 1;
+                    stdout.push(String::from_utf8(line).unwrap());
+                }
+            }
+            let expected: Vec<_> = (1..=200).map(|i| i.to_string()).collect();
+            assert!(events < 200);
+            assert_eq!(stdout.join("\n"), expected.join("\n"));
+        });
+    }
+
+    #[test]
+    fn test_event_buffer() {
+        let line = |line: &str| CommandEvent::Stdout(line.into());
+
+        let mut buffer = super::EventBuffer::new(8, super::OnEventOverflow::Block);
+        buffer.push(line("1234"));
+        assert!(!buffer.is_full());
+        buffer.push(line("5678"));
+        assert!(buffer.is_full());
+        assert!(matches!(buffer.pop_front(), Some(CommandEvent::Stdout(l)) if l == b"1234\n5678"));
+        assert!(!buffer.is_full());
+
+        let mut buffer = super::EventBuffer::new(8, super::OnEventOverflow::DropNewest);
+        buffer.push(line("1234"));
+        buffer.push(line("56789"));
+        buffer.push(line("567"));
+        buffer.push(CommandEvent::Progress(0.5));
+        buffer.push(CommandEvent::Error("error".into()));
+        assert!(!buffer.is_full());
+        assert!(matches!(buffer.pop_front(), Some(CommandEvent::Stdout(l)) if l == b"1234\n567"));
+        assert!(matches!(buffer.pop_front(), Some(CommandEvent::Error(_))));
+        assert!(buffer.pop_front().is_none());
+        assert_eq!(buffer.bytes, 0);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_progress() {
+        let cmd = Command::new("printf")
+            .args(["step 3/4\\n45%%\\ndone\\n"])
+            .progress_regex(
+                Regex::new(r"(?P<percent>\d+)%|(?P<current>\d+)/(?P<total>\d+)").unwrap(),
+            );
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        let mut events = Vec::new();
+        tauri::async_runtime::block_on(async {
+            while let Some(event) = rx.recv().await {
+                match event {
+                    CommandEvent::Stdout(line) => events.push(String::from_utf8(line).unwrap()),
+                    CommandEvent::Progress(progress) => events.push(progress.to_string()),
+                    _ => {}
+                }
+            }
+        });
+
+        assert_eq!(events, ["step 3/4", "0.75", "45%", "0.45", "done"]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_cmd_spawn_kill_on_consumer_drop() {
+        let cmd = Command::new("yes").on_consumer_drop(super::OnConsumerDrop::KillChild);
+        let (rx, child) = cmd.spawn().unwrap();
+        let pid = child.pid();
+        drop(rx);
+
+        let start = std::time::Instant::now();
+        // the killed process becomes a zombie until reaped, or disappears once it is
+        while std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .map(|stat| !stat.contains(") Z "))
+            .unwrap_or(false)
+        {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_output_to() {
+        let path = std::env::temp_dir().join("tauri-plugin-shell-test-output-to.txt");
+        let cmd = Command::new("cat").args(["test/test.txt"]).output_to(&path);
+        let (mut rx, _) = cmd.spawn().unwrap();
+
+        let mut stdout = Vec::new();
+        tauri::async_runtime::block_on(async {
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Stdout(line) = event {
+                    stdout.push(String::from_utf8(line).unwrap());
+                }
+            }
+        });
+
+        assert_eq!(stdout, ["This is a test doc!"]);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "This is a test doc!\n"
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_output_to_kill_on_consumer_drop() {
+        let path = std::env::temp_dir().join("tauri-plugin-shell-test-output-to-kill.txt");
+        let cmd = Command::new("yes")
+            .output_to(&path)
+            .on_consumer_drop(super::OnConsumerDrop::KillChild);
+        let (rx, child) = cmd.spawn().unwrap();
+        let pid = child.pid();
+        drop(rx);
+
+        let start = std::time::Instant::now();
+        while std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .map(|stat| !stat.contains(") Z "))
+            .unwrap_or(false)
+        {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_spawned_info() {
+        let env = [("API_TOKEN", "abc"), ("LANG", "C")]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let cmd = Command::new("echo")
+            .args(["hello world", "it's"])
+            .envs(env)
+            .env_clear()
+            .current_dir("/tmp/my dir".into());
+
+        let spawned = cmd.spawned_info();
+        assert_eq!(
+            spawned.env_diff,
+            [
+                ("API_TOKEN".to_string(), "***".to_string()),
+                ("LANG".to_string(), "C".to_string())
+            ]
+        );
+        assert_eq!(
+            spawned.to_string(),
+            r"cd '/tmp/my dir' && env -i API_TOKEN='***' LANG=C echo 'hello world' 'it'\''s'"
+        );
+
+        let output = tauri::async_runtime::block_on(Command::new("true").output()).unwrap();
+        assert_eq!(output.spawned.to_string(), "true");
+        match Command::new("tauri-plugin-shell-missing")
+            .args(["a b"])
+            .spawn()
+        {
+            Err(crate::Error::Spawn { spawned, .. }) => {
+                assert_eq!(spawned.to_string(), "tauri-plugin-shell-missing 'a b'")
+            }
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+
     #[cfg(not(windows))]
     #[test]
     fn test_cmd_spawn_raw_output() {
@@ -514,4 +1778,154 @@ mod tests {
             "cat: test/: Is a directory\n"
         );
     }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_policy_success_codes() {
+        let cmd = Command::new("cat").args(["test/"]).policy(ExecutionPolicy {
+            success_codes: vec![1],
+            ..Default::default()
+        });
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+
+        assert_eq!(output.status.code(), Some(1));
+        assert!(output.status.success());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_policy_retries() {
+        let path = std::env::temp_dir().join("tauri-plugin-shell-test-retries.txt");
+        // fails until its third attempt
+        let script = format!(
+            "n=$(($(cat {0} 2>/dev/null || echo 0) + 1)); echo $n > {0}; [ $n -ge 3 ]",
+            path.display()
+        );
+        let cmd = |retries| {
+            let _ = std::fs::remove_file(&path);
+            Command::new("sh")
+                .args(["-c", &script])
+                .policy(ExecutionPolicy {
+                    retries,
+                    backoff: Duration::from_millis(100),
+                    ..Default::default()
+                })
+        };
+
+        let start = std::time::Instant::now();
+        let status = tauri::async_runtime::block_on(cmd(2).status()).unwrap();
+        assert!(status.success());
+        // the backoff doubles from 100ms for the second retry
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "3\n");
+
+        let output = tauri::async_runtime::block_on(cmd(1).output()).unwrap();
+        assert!(!output.status.success());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "2\n");
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_policy_timeout() {
+        let cmd = Command::new("sleep").args(["10"]).policy(ExecutionPolicy {
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        let start = std::time::Instant::now();
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(!output.status.success());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_policy_expected_signals() {
+        let policy = ExecutionPolicy {
+            expected_signals: vec![15],
+            ..Default::default()
+        };
+        let terminated = Command::new("sh")
+            .args(["-c", "kill -TERM $$"])
+            .policy(policy.clone());
+        let status = tauri::async_runtime::block_on(terminated.status()).unwrap();
+        assert_eq!(status.signal(), Some(15));
+        assert!(status.success());
+
+        let killed = Command::new("sh")
+            .args(["-c", "kill -KILL $$"])
+            .policy(policy);
+        let status = tauri::async_runtime::block_on(killed.status()).unwrap();
+        assert_eq!(status.signal(), Some(9));
+        assert!(!status.success());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_spawn_terminated_timeout() {
+        let cmd = Command::new("sleep").args(["10"]).policy(ExecutionPolicy {
+            timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        });
+        let (mut rx, _child) = cmd.spawn().unwrap();
+
+        let mut terminated = Vec::new();
+        tauri::async_runtime::block_on(async {
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Terminated(payload) = event {
+                    terminated.push(payload);
+                }
+            }
+        });
+
+        assert_eq!(terminated.len(), 1);
+        assert_eq!(terminated[0].reason, super::TerminationReason::Timeout);
+        assert_eq!(terminated[0].code, None);
+        assert!(terminated[0].duration >= Duration::from_millis(100));
+        assert!(terminated[0].duration < Duration::from_secs(5));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_cmd_output_timeout_override() {
+        let cmd = Command::new("sleep")
+            .args(["1"])
+            .policy(ExecutionPolicy {
+                timeout: Some(Duration::from_millis(100)),
+                ..Default::default()
+            })
+            .timeout_override(Some(Duration::from_secs(10)));
+        let output = tauri::async_runtime::block_on(cmd.output()).unwrap();
+
+        assert!(output.status.success());
+    }
+
+    #[test]
+    fn test_normalize_line_endings() {
+        use std::io::{BufReader, Cursor};
+
+        // a capacity of 4 splits the first `\r\n` pair across two reads
+        let mut reader = BufReader::with_capacity(4, Cursor::new(b"abc\r\nde\r\nf\rg\n".to_vec()));
+        let mut line_endings = super::LineEndings::default();
+        let mut lines = Vec::new();
+        loop {
+            let mut buf = Vec::new();
+            let n = tauri::utils::io::read_line(&mut reader, &mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            lines.extend(line_endings.normalize(buf, n));
+        }
+
+        assert_eq!(
+            lines,
+            vec![
+                b"abc".to_vec(),
+                b"de".to_vec(),
+                b"f".to_vec(),
+                b"g".to_vec()
+            ]
+        );
+    }
 }
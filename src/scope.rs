@@ -3,12 +3,21 @@
 // SPDX-License-Identifier: MIT
 
 use crate::open::Program;
-use crate::process::Command;
-use crate::{Manager, Runtime};
+use crate::process::{
+    is_secret_env, ChildTracker, Command, CommandChild, CommandEvent, ExecutionPolicy,
+    InteractiveChild, IoPriority, Tracking,
+};
+use crate::{AppHandle, Manager, Runtime};
 
 use regex::Regex;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+};
+use std::time::SystemTime;
 
 /// Allowed representation of `Execute` command arguments.
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -23,15 +32,29 @@ pub enum ExecuteArgs {
 
     /// Multiple string arguments
     List(Vec<String>),
+
+    /// String arguments for the named variables of the command, using the names as the keys.
+    Map(HashMap<String, String>),
 }
 
 impl ExecuteArgs {
+    /// Splits a command line into a list of arguments like a POSIX shell does, without expanding
+    /// variables or globs.
+    ///
+    /// e.g. `commit -m "a message"` is split into `["commit", "-m", "a message"]`.
+    pub fn from_shell_str(line: &str) -> Result<Self, Error> {
+        shell_words::split(line)
+            .map(Self::List)
+            .map_err(|_| Error::ShellSplit(line.into()))
+    }
+
     /// Whether the argument list is empty or not.
     pub fn is_empty(&self) -> bool {
         match self {
             Self::None => true,
             Self::Single(s) if s.is_empty() => true,
             Self::List(l) => l.is_empty(),
+            Self::Map(m) => m.is_empty(),
             _ => false,
         }
     }
@@ -55,14 +78,207 @@ impl From<Vec<String>> for ExecuteArgs {
     }
 }
 
+impl From<HashMap<String, String>> for ExecuteArgs {
+    fn from(map: HashMap<String, String>) -> Self {
+        Self::Map(map)
+    }
+}
+
 /// Shell scope configuration.
 #[derive(Debug, Clone)]
 pub struct ScopeConfig {
     /// The validation regex that `shell > open` paths must match against.
     pub open: Option<Regex>,
 
+    /// The validation regex that revealed paths must match against, revealing is denied if `None`.
+    pub reveal: Option<Regex>,
+
+    /// The program used by `shell > open` calls without a specific program when the system has no
+    /// default application registered for the path.
+    pub open_default_program: Option<Program>,
+
+    /// The names of the programs `shell > open` calls may use, any program is allowed if `None`.
+    ///
+    /// An empty list only allows opening paths with their default application. The
+    /// [`Self::open_default_program`] must be listed too to be used as a fallback.
+    pub open_with: Option<Vec<String>>,
+
     /// All allowed commands, using their unique command name as the keys.
     pub scopes: HashMap<String, ScopeAllowedCommand>,
+
+    /// Whether a command path that fails to resolve is an [`Error::UnresolvedPath`] error.
+    ///
+    /// When disabled, such paths keep their configured value.
+    pub strict_paths: bool,
+
+    /// The maximum number of commands in [`Self::scopes`], a scope with more commands fails to be
+    /// created with [`Error::TooManyCommands`].
+    pub max_commands: usize,
+
+    /// Defaults of the command groups, using the group name as the keys.
+    pub groups: HashMap<String, CommandGroupDefaults>,
+}
+
+/// The default of [`ScopeConfig::max_commands`].
+pub const DEFAULT_MAX_COMMANDS: usize = 4096;
+
+impl ScopeConfig {
+    /// Derives a config with every safety option of the scope enabled for all commands.
+    ///
+    /// Compared to `base`, the derived config:
+    /// - anchors every argument validator regex, including the `anyOf` ones, the validators of the
+    ///   [`ScopeAllowedCommand::env`] variables, and the `open` and `reveal` regexes, so they must
+    ///   match the whole value instead of a part of it;
+    /// - rejects variable values starting with `-` or containing a NUL byte;
+    /// - removes the [`DANGEROUS_ENV`] variables from the environment of the commands;
+    /// - rejects calls passing more arguments than configured.
+    ///
+    /// An anchored regex that does not compile, which can only happen with patterns ending in a
+    /// comment of the `x` flag, is an [`Error::InvalidConfig`] error.
+    pub fn hardened(mut base: ScopeConfig) -> Result<ScopeConfig, Error> {
+        fn anchor(regex: &Regex) -> Result<Regex, Error> {
+            let anchored = format!("^(?:{})$", regex.as_str());
+            Regex::new(&anchored)
+                .map_err(|e| Error::InvalidConfig(format!("invalid regex {anchored}: {e}")))
+        }
+
+        base.open = base.open.as_ref().map(anchor).transpose()?;
+        base.reveal = base.reveal.as_ref().map(anchor).transpose()?;
+        for command in base.scopes.values_mut() {
+            for validator in command.env.iter_mut().flat_map(HashMap::values_mut) {
+                *validator = anchor(validator)?;
+            }
+            command.hardening = Hardening {
+                reject_leading_dash: true,
+                reject_nul: true,
+                strip_dangerous_env: true,
+                reject_extra_args: true,
+            };
+            for arg in command.args.iter_mut().flatten() {
+                match arg {
+                    ScopeAllowedArg::Var { validator, .. } => *validator = anchor(validator)?,
+                    ScopeAllowedArg::AnyOf(validators) => {
+                        for validator in validators {
+                            *validator = anchor(validator)?;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(base)
+    }
+}
+
+#[cfg(feature = "signed-config")]
+impl ScopeConfig {
+    /// Loads a scope config file after verifying its detached signature.
+    ///
+    /// The file has the format of the plugin configuration (`plugins > shell`), and its ed25519
+    /// signature is read from the same path with a `.sig` extension appended, see [`Self::sign_file`].
+    /// A signature that does not match the file content is an [`Error::InvalidSignature`] error,
+    /// and a signed config with invalid values is an [`Error::InvalidConfig`] error.
+    pub fn from_file_signed<P: AsRef<std::path::Path>>(
+        path: P,
+        public_key: &ed25519_dalek::VerifyingKey,
+    ) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = std::fs::read(path)?;
+        let signature = std::fs::read(signature_path(path))?;
+        let signature = ed25519_dalek::Signature::from_slice(&signature)
+            .map_err(|_| Error::InvalidSignature(path.into()))?;
+        public_key
+            .verify_strict(&content, &signature)
+            .map_err(|_| Error::InvalidSignature(path.into()))?;
+        let config: crate::config::Config =
+            serde_json::from_slice(&content).map_err(|e| Error::InvalidConfig(e.to_string()))?;
+        crate::shell_scope(&config)
+    }
+
+    /// Signs a scope config file, writing the detached signature [`Self::from_file_signed`] verifies.
+    pub fn sign_file<P: AsRef<std::path::Path>>(
+        path: P,
+        signing_key: &ed25519_dalek::SigningKey,
+    ) -> Result<(), Error> {
+        use ed25519_dalek::Signer;
+
+        let path = path.as_ref();
+        let signature = signing_key.sign(&std::fs::read(path)?);
+        std::fs::write(signature_path(path), signature.to_bytes())?;
+        Ok(())
+    }
+}
+
+/// The path of the detached signature of a scope config file.
+#[cfg(feature = "signed-config")]
+fn signature_path(path: &std::path::Path) -> PathBuf {
+    let mut signature = path.as_os_str().to_owned();
+    signature.push(".sig");
+    signature.into()
+}
+
+/// Defaults inherited by the scoped commands referencing a group, see [`ScopeAllowedCommand::group`].
+#[derive(Debug, Clone, Default)]
+pub struct CommandGroupDefaults {
+    /// The shell command used by the commands with an empty `command`.
+    pub command: Option<PathBuf>,
+
+    /// The execution policy used by the commands without one.
+    pub policy: Option<ExecutionPolicy>,
+
+    /// The disk I/O priority used by the commands without one.
+    pub io_priority: Option<IoPriority>,
+
+    /// The working directory used by the commands inheriting the working directory of the app.
+    pub cwd: Option<CwdMode>,
+}
+
+impl CommandGroupDefaults {
+    /// Fills the values the command did not set with the group defaults.
+    fn apply(&self, command: &mut ScopeAllowedCommand) {
+        if command.command.as_os_str().is_empty() {
+            if let Some(program) = &self.command {
+                command.command = program.clone();
+            }
+        }
+        if command.policy.is_none() {
+            command.policy = self.policy.clone();
+        }
+        if command.io_priority.is_none() {
+            command.io_priority = self.io_priority;
+        }
+        if command.cwd == CwdMode::Inherit {
+            if let Some(cwd) = &self.cwd {
+                command.cwd = cwd.clone();
+            }
+        }
+    }
+}
+
+/// The working directory of a scoped command, resolved with the other scoped paths.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CwdMode {
+    /// The working directory of the app.
+    #[default]
+    Inherit,
+    /// The app data directory, `$APPDATA`.
+    AppDir,
+    /// The resource directory, `$RESOURCE`.
+    ResourceDir,
+    /// A path, which can start with a base directory variable like [`ScopeAllowedCommand::command`].
+    Path(PathBuf),
+}
+
+impl CwdMode {
+    /// The path to resolve for this working directory, `None` to inherit the app one.
+    fn path(&self) -> Option<PathBuf> {
+        match self {
+            Self::Inherit => None,
+            Self::AppDir => Some("$APPDATA".into()),
+            Self::ResourceDir => Some("$RESOURCE".into()),
+            Self::Path(path) => Some(path.clone()),
+        }
+    }
 }
 
 /// A configured scoped shell command.
@@ -76,6 +292,199 @@ pub struct ScopeAllowedCommand {
 
     /// If this command is a sidecar command.
     pub sidecar: bool,
+
+    /// If this command can be spawned as an interactive session, see [`Scope::spawn_interactive`].
+    pub allow_stdin: bool,
+
+    /// If only one instance of this command can run at a time.
+    ///
+    /// Preparing or spawning the command while a previous instance is still running returns an
+    /// [`Error::AlreadyRunning`] error. A prepared command only counts as running once it is
+    /// spawned.
+    pub singleton: bool,
+
+    /// The execution policy of the prepared command, which can still be replaced per call with
+    /// [`Command::policy`]. Commands without one use the policy of their group, or the default
+    /// policy.
+    pub policy: Option<ExecutionPolicy>,
+
+    /// The disk I/O priority of the prepared command.
+    pub io_priority: Option<IoPriority>,
+
+    /// The group this command inherits its defaults from, merged when the [`Scope`] is created.
+    pub group: Option<String>,
+
+    /// The capability tags of the command, see [`Scope::set_tag_enabled`].
+    pub tags: Vec<String>,
+
+    /// The working directory of the prepared command.
+    pub cwd: CwdMode,
+
+    /// Constraints between the argument values, checked once each argument passed its validation.
+    ///
+    /// Arguments violating a constraint are an [`Error::CrossValidation`] error, and a constraint
+    /// referencing a position beyond [`Self::args`] fails the scope creation with an
+    /// [`Error::InvalidConfig`] error.
+    pub cross_validators: Vec<CrossValidator>,
+
+    /// How an [`ExecuteArgs::Single`] string is handled when the command has a list of arguments.
+    pub single_arg_mode: SingleArgMode,
+
+    /// A marker argument after which the caller arguments are passed through unchanged.
+    ///
+    /// Only the arguments before the marker are validated against [`Self::args`], the marker and
+    /// the arguments after it are appended as is, but must not contain control characters. The
+    /// marker is only looked for after the positions of [`Self::args`], so that a variable can
+    /// have the marker as its value.
+    pub passthrough_after: Option<String>,
+
+    /// The regex parsing the progress of the command from its output, see
+    /// [`Command::progress_regex`].
+    pub progress_regex: Option<Regex>,
+
+    /// Whether a caller argument beyond the positions of [`Self::args`] is an
+    /// [`Error::TooManyArgs`] error, including a [`Self::passthrough_after`] marker.
+    ///
+    /// Has no effect on commands accepting any arguments.
+    pub strict_positions: bool,
+
+    /// The environment variables callers may set with [`Scope::prepare_with_env`], mapped to the
+    /// regex validating their values. No variable may be set if `None`.
+    pub env: Option<HashMap<String, Regex>>,
+
+    /// Additional safety checks of the command, see [`ScopeConfig::hardened`].
+    pub hardening: Hardening,
+}
+
+/// Additional safety checks of a scoped command, all disabled by default.
+///
+/// The value checks apply to the values passed by the caller for the variable arguments, or to all
+/// the arguments of a command accepting any arguments, but not to passed through arguments.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Hardening {
+    /// Reject values starting with `-`, which the program could take for an option.
+    pub reject_leading_dash: bool,
+    /// Reject values containing a NUL byte.
+    pub reject_nul: bool,
+    /// Remove the [`DANGEROUS_ENV`] variables from the environment of the command.
+    pub strip_dangerous_env: bool,
+    /// Reject calls with more arguments than configured, instead of ignoring the extra ones, with
+    /// an [`Error::TooManyArgs`] error. Unlike [`ScopeAllowedCommand::strict_positions`], passed
+    /// through arguments are still allowed.
+    pub reject_extra_args: bool,
+}
+
+impl Hardening {
+    /// Checks a value passed by the caller at `index`.
+    fn check(&self, index: usize, value: &str) -> Result<(), Error> {
+        let validation = if self.reject_leading_dash && value.starts_with('-') {
+            "no leading dash"
+        } else if self.reject_nul && value.contains('\0') {
+            "no NUL byte"
+        } else {
+            return Ok(());
+        };
+        Err(Error::Validation {
+            index,
+            validation: validation.into(),
+        })
+    }
+}
+
+/// Environment variables that make programs load code or configuration from another location,
+/// removed from hardened commands.
+pub const DANGEROUS_ENV: &[&str] = &[
+    "LD_PRELOAD",
+    "LD_LIBRARY_PATH",
+    "LD_AUDIT",
+    "DYLD_INSERT_LIBRARIES",
+    "DYLD_LIBRARY_PATH",
+    "DYLD_FRAMEWORK_PATH",
+    "BASH_ENV",
+    "ENV",
+    "NODE_OPTIONS",
+    "PYTHONPATH",
+    "PYTHONSTARTUP",
+    "PERL5OPT",
+    "PERL5LIB",
+    "RUBYOPT",
+];
+
+/// How a scoped command with a list of arguments handles an [`ExecuteArgs::Single`] string.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SingleArgMode {
+    /// The string is an [`Error::InvalidInput`] error.
+    #[default]
+    Reject,
+    /// The string is split with [`ExecuteArgs::from_shell_str`], then validated as a list.
+    SplitShell,
+}
+
+/// A constraint between the numeric values passed at two positions of a [`ScopeAllowedArg`] list.
+///
+/// Values that are not numbers fail the validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrossValidator {
+    /// The position of the argument on the left side of the relation.
+    pub left: usize,
+    /// The relation the values must have.
+    pub relation: Relation,
+    /// The position of the argument on the right side of the relation.
+    pub right: usize,
+}
+
+/// A relation between two values of a [`CrossValidator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// Less than.
+    Lt,
+    /// Less than or equal to.
+    Le,
+    /// Equal to.
+    Eq,
+    /// Not equal to.
+    Ne,
+    /// Greater than or equal to.
+    Ge,
+    /// Greater than.
+    Gt,
+}
+
+impl std::fmt::Display for Relation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Eq => "==",
+            Self::Ne => "!=",
+            Self::Ge => ">=",
+            Self::Gt => ">",
+        })
+    }
+}
+
+impl CrossValidator {
+    /// Whether the values at the `left` and `right` positions satisfy the relation.
+    fn check(&self, list: &[ScopeAllowedArg], args: &[String]) -> bool {
+        let value = |index: usize| {
+            let value = match list.get(index)? {
+                ScopeAllowedArg::Fixed(fixed) => fixed,
+                _ => args.get(index)?,
+            };
+            value.parse::<f64>().ok()
+        };
+        let (Some(left), Some(right)) = (value(self.left), value(self.right)) else {
+            return false;
+        };
+        match self.relation {
+            Relation::Lt => left < right,
+            Relation::Le => left <= right,
+            Relation::Eq => left == right,
+            Relation::Ne => left != right,
+            Relation::Ge => left >= right,
+            Relation::Gt => left > right,
+        }
+    }
 }
 
 /// A configured argument to a scoped shell command.
@@ -88,19 +497,663 @@ pub enum ScopeAllowedArg {
     Var {
         /// The validation that the variable value must pass in order to be called.
         validator: Regex,
+
+        /// The maximum length of the variable value in bytes, checked before the regex validation.
+        ///
+        /// This bounds a single argument only, the length of the whole command line is not limited.
+        max_len: Option<usize>,
+
+        /// Named capture groups of the validator that must capture a non-empty value.
+        require_captures: Vec<String>,
+
+        /// Whether the validated value is converted with [`to_native_path`] before being passed on.
+        to_native_path: bool,
+
+        /// The name of the variable, to pass its value by name with [`ExecuteArgs::Map`].
+        name: Option<String>,
+    },
+
+    /// An argument with a value to be evaluated at runtime, must match at least one of the regexes.
+    AnyOf(Vec<Regex>),
+
+    /// An argument with a value to be evaluated at runtime, must be one of the map keys.
+    ///
+    /// The value is replaced by the arguments it maps to, e.g. `high` by `["-vvv"]`.
+    Alias(HashMap<String, Vec<String>>),
+
+    /// An argument with a value to be evaluated at runtime, must be an RFC 4122 UUID.
+    ///
+    /// The value is passed on in its canonical lowercase, hyphenated form.
+    Uuid {
+        /// The UUID version the value must have, any version is accepted if not set.
+        version: Option<u8>,
+    },
+
+    /// An argument with a value to be evaluated at runtime, must be a well-formed email address.
+    ///
+    /// Display names such as `Jane <jane@example.com>` are rejected. The value is passed on with its
+    /// domain lowercased, the local part is kept as is.
+    Email,
+
+    /// An argument with a value to be evaluated at runtime, must be an RFC 1123 hostname.
+    ///
+    /// The value is passed on lowercased, or in its canonical form for IP addresses.
+    Hostname {
+        /// Whether IPv4 and IPv6 addresses are accepted as well.
+        allow_ip: bool,
+    },
+
+    /// An argument with a value to be evaluated at runtime, must be the path of a file of at most
+    /// `bytes` bytes.
+    ///
+    /// Relative paths are resolved against the working directory of the command, see
+    /// [`ScopeAllowedCommand::cwd`]. A file that cannot be read is an [`Error::Io`] error.
+    FileSizeMax {
+        /// The maximum size of the file in bytes.
+        bytes: u64,
     },
 }
 
 impl ScopeAllowedArg {
+    /// A variable argument validated by `regex`, without the optional checks.
+    pub fn var(regex: Regex) -> Self {
+        Self::Var {
+            validator: regex,
+            max_len: None,
+            require_captures: Vec::new(),
+            to_native_path: false,
+            name: None,
+        }
+    }
+
+    /// The name of the argument, if it is a named variable.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Self::Var { name, .. } => name.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// A variable argument validated by the regex `pattern`, compiled like the configured ones.
+    ///
+    /// A pattern that does not compile is an [`Error::InvalidConfig`] error.
+    pub fn var_str(pattern: &str) -> Result<Self, Error> {
+        Ok(Self::var(compile_regex(pattern)?))
+    }
+
     /// If the argument is fixed.
     pub fn is_fixed(&self) -> bool {
         matches!(self, Self::Fixed(_))
     }
+
+    /// Validates the value passed at `index` for this argument, pushing the arguments to call with.
+    ///
+    /// `cwd` is the working directory of the command, if it does not inherit the app one.
+    fn resolve(
+        &self,
+        index: usize,
+        value: Option<&String>,
+        cwd: Option<&std::path::Path>,
+        argv: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        match self {
+            Self::Fixed(fixed) => argv.push(fixed.to_string()),
+            Self::Var {
+                validator,
+                max_len,
+                require_captures,
+                to_native_path: native,
+                ..
+            } => {
+                let value = value.ok_or_else(|| Error::MissingVar(index, validator.to_string()))?;
+                if let Some(max_len) = max_len.filter(|max_len| value.len() > *max_len) {
+                    return Err(Error::Validation {
+                        index,
+                        validation: format!("at most {max_len} bytes long"),
+                    });
+                }
+                let captures = validator.captures(value).ok_or_else(|| Error::Validation {
+                    index,
+                    validation: validator.to_string(),
+                })?;
+                if let Some(name) = require_captures.iter().find(|name| {
+                    captures
+                        .name(name)
+                        .filter(|capture| !capture.as_str().is_empty())
+                        .is_none()
+                }) {
+                    return Err(Error::Validation {
+                        index,
+                        validation: format!("{validator} with a non-empty `{name}` capture"),
+                    });
+                }
+                if *native {
+                    argv.push(to_native_path(value));
+                } else {
+                    argv.push(value.to_string());
+                }
+            }
+            Self::AnyOf(validators) => {
+                let validation = format!(
+                    "any of [{}]",
+                    validators
+                        .iter()
+                        .map(Regex::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                let value = value.ok_or_else(|| Error::MissingVar(index, validation.clone()))?;
+                if validators.iter().any(|validator| validator.is_match(value)) {
+                    argv.push(value.to_string());
+                } else {
+                    return Err(Error::Validation { index, validation });
+                }
+            }
+            Self::Alias(aliases) => {
+                let mut names: Vec<_> = aliases.keys().map(String::as_str).collect();
+                names.sort_unstable();
+                let validation = format!("one of [{}]", names.join(", "));
+                let value = value.ok_or_else(|| Error::MissingVar(index, validation.clone()))?;
+                match aliases.get(value) {
+                    Some(mapped) => argv.extend(mapped.iter().cloned()),
+                    None => return Err(Error::Validation { index, validation }),
+                }
+            }
+            Self::Uuid { version } => {
+                let validation = match version {
+                    Some(version) => format!("version {version} UUID"),
+                    None => "UUID".to_string(),
+                };
+                let value = value.ok_or_else(|| Error::MissingVar(index, validation.clone()))?;
+                let uuid = uuid::Uuid::parse_str(value)
+                    .ok()
+                    .filter(|uuid| uuid.get_variant() == uuid::Variant::RFC4122)
+                    .filter(|uuid| match version {
+                        Some(version) => uuid.get_version_num() == usize::from(*version),
+                        None => true,
+                    })
+                    .ok_or(Error::Validation { index, validation })?;
+                argv.push(uuid.hyphenated().to_string());
+            }
+            Self::Email => {
+                let validation = "email address".to_string();
+                let value = value.ok_or_else(|| Error::MissingVar(index, validation.clone()))?;
+                // a display name would smuggle arbitrary text next to the address
+                let email = email_address::EmailAddress::parse_with_options(
+                    value,
+                    email_address::Options::default().without_display_text(),
+                )
+                .map_err(|_| Error::Validation { index, validation })?;
+                argv.push(format!(
+                    "{}@{}",
+                    email.local_part(),
+                    email.domain().to_lowercase()
+                ));
+            }
+            Self::Hostname { allow_ip } => {
+                let validation = if *allow_ip {
+                    "hostname or IP address"
+                } else {
+                    "hostname"
+                }
+                .to_string();
+                let value = value.ok_or_else(|| Error::MissingVar(index, validation.clone()))?;
+                match value.parse::<std::net::IpAddr>() {
+                    Ok(ip) if *allow_ip => argv.push(ip.to_string()),
+                    _ if is_hostname(value) => argv.push(value.to_ascii_lowercase()),
+                    _ => return Err(Error::Validation { index, validation }),
+                }
+            }
+            Self::FileSizeMax { bytes } => {
+                let validation = format!("file of at most {bytes} bytes");
+                let value = value.ok_or_else(|| Error::MissingVar(index, validation.clone()))?;
+                // the command receives the path as is, so it must be checked where the command runs
+                let metadata = match cwd {
+                    Some(cwd) => std::fs::metadata(cwd.join(value))?,
+                    None => std::fs::metadata(value)?,
+                };
+                if !metadata.is_file() || metadata.len() > *bytes {
+                    return Err(Error::Validation { index, validation });
+                }
+                argv.push(value.to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compiles a configured regex, failing with an [`Error::InvalidConfig`] error.
+pub(crate) fn compile_regex(pattern: &str) -> Result<Regex, Error> {
+    Regex::new(pattern).map_err(|e| Error::InvalidConfig(format!("invalid regex {pattern}: {e}")))
+}
+
+/// Whether the value is a hostname as defined by RFC 1123.
+///
+/// Labels are 1 to 63 letters, digits and hyphens, not starting or ending with a hyphen, and the
+/// whole name is at most 253 bytes. The last label must not be numeric, so the name cannot be
+/// mistaken for an IPv4 address.
+fn is_hostname(value: &str) -> bool {
+    let labels: Vec<_> = value.split('.').collect();
+    value.len() <= 253
+        && labels.iter().all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+        && labels
+            .last()
+            .filter(|label| label.bytes().all(|b| b.is_ascii_digit()))
+            .is_none()
+}
+
+/// Converts a path using `/` or `\` separators to the platform-native separators.
+///
+/// The normalization is textual only, the filesystem is not accessed: empty and `.` components are
+/// removed, and `..` components remove the previous component, or are kept at the start of a
+/// relative path. A leading separator (or two, for UNC paths) and a drive prefix such as `C:` are
+/// preserved.
+pub fn to_native_path(path: &str) -> String {
+    let is_separator = |c: char| c == '/' || c == '\\';
+    let mut native = String::new();
+    let mut rest = path;
+    let bytes = path.as_bytes();
+    if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        native.push_str(&path[..2]);
+        rest = &path[2..];
+    }
+    let leading = rest.chars().take_while(|c| is_separator(*c)).count().min(2);
+    let rooted = leading > 0;
+    for _ in 0..leading {
+        native.push(std::path::MAIN_SEPARATOR);
+    }
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split(is_separator) {
+        match component {
+            "" | "." => {}
+            ".." => match components.last() {
+                Some(&last) if last != ".." => {
+                    components.pop();
+                }
+                // a rooted path cannot go above its root
+                _ if rooted => {}
+                _ => components.push(".."),
+            },
+            component => components.push(component),
+        }
+    }
+
+    if native.is_empty() && components.is_empty() {
+        return ".".into();
+    }
+    for (i, component) in components.iter().enumerate() {
+        if i > 0 {
+            native.push(std::path::MAIN_SEPARATOR);
+        }
+        native.push_str(component);
+    }
+    native
+}
+
+/// A middleware rewriting the validated arguments of a scoped command, see
+/// [`Scope::add_arg_middleware`].
+pub type ArgMiddleware = Box<dyn Fn(&str, Vec<String>) -> Result<Vec<String>, Error> + Send + Sync>;
+
+/// Where a regex reported by [`Scope::regex_report`] is used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegexLocation {
+    /// The `shell > open` validation.
+    Open,
+    /// The `shell > reveal` validation.
+    Reveal,
+    /// The validation of an argument of a scoped command.
+    Argument {
+        /// The name of the scoped command.
+        command: String,
+        /// The position of the argument.
+        index: usize,
+    },
+    /// The validation of an environment variable of a scoped command.
+    Env {
+        /// The name of the scoped command.
+        command: String,
+        /// The name of the variable.
+        name: String,
+    },
+    /// The progress regex of a scoped command.
+    Progress {
+        /// The name of the scoped command.
+        command: String,
+    },
+}
+
+/// The source and complexity metrics of a regex of the scope, see [`Scope::regex_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexInfo {
+    /// Where the regex is used.
+    pub location: RegexLocation,
+    /// The pattern source.
+    pub pattern: String,
+    /// The inline flags the pattern enables anywhere, e.g. `ix` for `(?i)a(?x: b )`, in the order
+    /// `imsRUux`.
+    pub flags: String,
+    /// The smallest compiled size limit in bytes the regex builds with, see
+    /// [`regex::RegexBuilder::size_limit`].
+    pub compiled_size: usize,
+    /// The number of nodes of the parsed pattern.
+    pub nodes: usize,
+    /// How deeply repetitions are nested in each other, nested unbounded repetitions being a
+    /// common source of slow patterns.
+    pub repetition_depth: usize,
+    /// The number of repetitions without an upper bound, like `*` and `+`.
+    pub unbounded_repetitions: usize,
+    /// The minimum length in bytes of a match.
+    pub min_len: Option<usize>,
+    /// The maximum length in bytes of a match, `None` if unbounded.
+    pub max_len: Option<usize>,
+    /// Whether every match must start at the beginning of the value.
+    pub anchored_start: bool,
+    /// Whether every match must end at the end of the value.
+    pub anchored_end: bool,
+}
+
+impl RegexInfo {
+    fn new(location: RegexLocation, regex: &Regex) -> Self {
+        let pattern = regex.as_str();
+        let mut info = Self {
+            location,
+            pattern: pattern.into(),
+            flags: String::new(),
+            compiled_size: compiled_size(pattern),
+            nodes: 0,
+            repetition_depth: 0,
+            unbounded_repetitions: 0,
+            min_len: None,
+            max_len: None,
+            anchored_start: false,
+            anchored_end: false,
+        };
+        // the pattern was compiled already, so it parses
+        if let Ok(hir) = regex_syntax::parse(pattern) {
+            info.repetition_depth = info.visit(&hir, 0);
+            let properties = hir.properties();
+            info.min_len = properties.minimum_len();
+            info.max_len = properties.maximum_len();
+            info.anchored_start = properties
+                .look_set_prefix()
+                .contains(regex_syntax::hir::Look::Start);
+            info.anchored_end = properties
+                .look_set_suffix()
+                .contains(regex_syntax::hir::Look::End);
+        }
+        if let Ok(ast) = regex_syntax::ast::parse::Parser::new().parse(pattern) {
+            let enabled =
+                regex_syntax::ast::visit(&ast, FlagVisitor::default()).unwrap_or_default();
+            info.flags = FLAGS
+                .iter()
+                .zip(enabled)
+                .filter(|(_, enabled)| *enabled)
+                .map(|((_, name), _)| *name)
+                .collect();
+        }
+        info
+    }
+
+    /// Counts the nodes and unbounded repetitions of `hir`, returning its repetition depth.
+    fn visit(&mut self, hir: &regex_syntax::hir::Hir, depth: usize) -> usize {
+        use regex_syntax::hir::HirKind;
+
+        self.nodes += 1;
+        match hir.kind() {
+            HirKind::Repetition(repetition) => {
+                if repetition.max.is_none() {
+                    self.unbounded_repetitions += 1;
+                }
+                self.visit(&repetition.sub, depth + 1)
+            }
+            HirKind::Capture(capture) => self.visit(&capture.sub, depth),
+            HirKind::Concat(subs) | HirKind::Alternation(subs) => subs
+                .iter()
+                .map(|sub| self.visit(sub, depth))
+                .max()
+                .unwrap_or(depth),
+            _ => depth,
+        }
+    }
+}
+
+/// The inline flags reported by [`RegexInfo::flags`], in the reported order.
+const FLAGS: [(regex_syntax::ast::Flag, char); 7] = {
+    use regex_syntax::ast::Flag;
+    [
+        (Flag::CaseInsensitive, 'i'),
+        (Flag::MultiLine, 'm'),
+        (Flag::DotMatchesNewLine, 's'),
+        (Flag::CRLF, 'R'),
+        (Flag::SwapGreed, 'U'),
+        (Flag::Unicode, 'u'),
+        (Flag::IgnoreWhitespace, 'x'),
+    ]
+};
+
+/// Collects which of the [`FLAGS`] a pattern enables, in a flag group or a non-capturing group.
+#[derive(Default)]
+struct FlagVisitor([bool; 7]);
+
+impl regex_syntax::ast::Visitor for FlagVisitor {
+    type Output = [bool; 7];
+    type Err = std::convert::Infallible;
+
+    fn finish(self) -> Result<Self::Output, Self::Err> {
+        Ok(self.0)
+    }
+
+    fn visit_pre(&mut self, ast: &regex_syntax::ast::Ast) -> Result<(), Self::Err> {
+        use regex_syntax::ast::{Ast, GroupKind};
+
+        let flags = match ast {
+            Ast::Flags(set) => &set.flags,
+            Ast::Group(group) => match &group.kind {
+                GroupKind::NonCapturing(flags) => flags,
+                _ => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+        for ((flag, _), enabled) in FLAGS.iter().zip(self.0.iter_mut()) {
+            if flags.flag_state(*flag) == Some(true) {
+                *enabled = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The smallest size limit the pattern compiles with, found by bisecting the default limit.
+fn compiled_size(pattern: &str) -> usize {
+    let (mut low, mut high) = (0, 10 * (1 << 20));
+    while low < high {
+        let limit = low + (high - low) / 2;
+        if regex::RegexBuilder::new(pattern)
+            .size_limit(limit)
+            .build()
+            .is_ok()
+        {
+            high = limit;
+        } else {
+            low = limit + 1;
+        }
+    }
+    low
+}
+
+/// A stage of a pipeline checked by [`Scope::pipeline_preflight`].
+#[derive(Debug, Clone)]
+pub struct PipelineStage {
+    /// The name of the scoped command to call.
+    pub command: String,
+
+    /// The arguments the command is called with.
+    pub args: ExecuteArgs,
+
+    /// If the command is called as a sidecar.
+    pub sidecar: bool,
+}
+
+/// The status reported by the launcher that opened a path, see [`Scope::open_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpenStatus {
+    /// The launcher exited successfully, the path is being opened.
+    Launched {
+        /// The launcher program, e.g. `xdg-open`.
+        launcher: String,
+    },
+    /// The launcher ran but reported a failure, e.g. because no application is associated with the path.
+    Failed {
+        /// The launcher program, e.g. `xdg-open`.
+        launcher: String,
+        /// The exit code of the launcher, if it was not terminated by a signal.
+        code: Option<i32>,
+    },
+}
+
+impl OpenStatus {
+    /// Maps a launcher failure to an [`Error::LaunchFailed`] error.
+    fn into_result(self) -> Result<(), Error> {
+        match self {
+            Self::Launched { .. } => Ok(()),
+            Self::Failed { launcher, code } => Err(Error::LaunchFailed { launcher, code }),
+        }
+    }
+}
+
+/// Shows the path selected in Finder.
+#[cfg(target_os = "macos")]
+fn reveal_path(path: &std::path::Path) -> Result<(), Error> {
+    let mut command = std::process::Command::new("open");
+    command.arg("-R").arg(path);
+    launch(command)?.into_result()
+}
+
+/// Shows the path selected in the Windows Explorer.
+#[cfg(windows)]
+fn reveal_path(path: &std::path::Path) -> Result<(), Error> {
+    use std::os::windows::process::CommandExt;
+
+    // explorer reports a failure even when it selected the path, so only spawning it can fail
+    std::process::Command::new("explorer")
+        .raw_arg(format!("/select,\"{}\"", path.display()))
+        .spawn()?;
+    Ok(())
+}
+
+/// Shows the path selected in the file manager implementing the `org.freedesktop.FileManager1`
+/// D-Bus interface, or opens its parent directory if there is none.
+#[cfg(not(any(target_os = "macos", windows)))]
+fn reveal_path(path: &std::path::Path) -> Result<(), Error> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = path.canonicalize()?;
+    let mut uri = String::from("file://");
+    for byte in path.as_os_str().as_bytes() {
+        match byte {
+            b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(*byte as char),
+            byte if byte.is_ascii_alphanumeric() => uri.push(*byte as char),
+            byte => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    let mut command = std::process::Command::new("dbus-send");
+    command
+        .args([
+            "--session",
+            "--print-reply",
+            "--dest=org.freedesktop.FileManager1",
+            "--type=method_call",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+        ])
+        .arg(format!("array:string:{uri}"))
+        .arg("string:");
+    match launch(command) {
+        Ok(OpenStatus::Launched { .. }) => Ok(()),
+        // no D-Bus session or no file manager implementing the interface
+        _ => ::open::that(path.parent().unwrap_or(&path)).map_err(Into::into),
+    }
+}
+
+/// Runs an `open` launcher to completion, mapping its exit status.
+fn launch(mut command: std::process::Command) -> std::io::Result<OpenStatus> {
+    let launcher = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()?;
+    Ok(if status.success() {
+        OpenStatus::Launched { launcher }
+    } else {
+        OpenStatus::Failed {
+            launcher,
+            code: status.code(),
+        }
+    })
+}
+
+/// A sink receiving the security events of a scope, see [`Scope::set_audit_sink`].
+pub type AuditSink = Arc<dyn Fn(AuditEvent) + Send + Sync>;
+
+/// A scoped command or path that was denied, or allowed when successes are audited.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    /// What was checked.
+    pub action: AuditAction,
+    /// The name of the scoped command, or the program a path is opened with, empty for the default
+    /// application and for revealed paths.
+    pub command: String,
+    /// The arguments the command was called with, before validation, or the path.
+    pub args: Vec<String>,
+    /// If the command was called as a sidecar.
+    pub sidecar: bool,
+    /// Whether the command was allowed.
+    pub outcome: AuditOutcome,
+    /// When the command was checked.
+    pub timestamp: SystemTime,
+}
+
+/// What an [`AuditEvent`] was checked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    /// Preparing a scoped command.
+    Execute,
+    /// Opening a path, see [`Scope::open`].
+    Open,
+    /// Revealing a path, see [`Scope::reveal`].
+    Reveal,
+}
+
+/// The outcome of an [`AuditEvent`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditOutcome {
+    /// The command passed validation.
+    Allowed,
+    /// The command was denied, with the reason.
+    Denied(String),
 }
 
 /// Scope for filesystem access.
 #[derive(Clone)]
-pub struct Scope(ScopeConfig);
+pub struct Scope {
+    config: ScopeConfig,
+    paths: Arc<RwLock<HashMap<String, PathBuf>>>,
+    cwds: Arc<RwLock<HashMap<String, PathBuf>>>,
+    children: HashMap<String, Arc<ChildTracker>>,
+    middlewares: Arc<RwLock<Vec<ArgMiddleware>>>,
+    disabled_tags: Arc<RwLock<HashSet<String>>>,
+    audit_sink: Arc<RwLock<Option<AuditSink>>>,
+    audit_successes: Arc<AtomicBool>,
+}
 
 /// All errors that can happen while validating a scoped command.
 #[derive(Debug, thiserror::Error)]
@@ -119,6 +1172,28 @@ pub enum Error {
     #[error("Scoped command {0} not found")]
     NotFound(String),
 
+    /// A command line could not be split into arguments, e.g. because of an unterminated quote.
+    #[error("The arguments {0} could not be split like a shell does")]
+    ShellSplit(String),
+
+    /// The path or working directory of a scoped command could not be resolved.
+    #[error("The path {} of scoped command {command} could not be resolved", path.display())]
+    UnresolvedPath {
+        /// Name of the scoped command.
+        command: String,
+
+        /// The configured path that failed to resolve.
+        path: PathBuf,
+    },
+
+    /// The named singleton command is already running.
+    #[error("Scoped command {0} is already running")]
+    AlreadyRunning(String),
+
+    /// The named command is not allowed to be spawned as an interactive session.
+    #[error("Scoped command {0} is not allowed to receive stdin")]
+    StdinNotAllowed(String),
+
     /// A command variable has no value set in the arguments.
     #[error(
     "Scoped command argument at position {0} must match regex validation {1} but it was not found"
@@ -126,12 +1201,14 @@ pub enum Error {
     MissingVar(usize, String),
 
     /// At least one argument did not pass input validation.
-    #[error("Scoped command argument at position {index} was found, but failed regex validation {validation}")]
+    #[error(
+        "Scoped command argument at position {index} was found, but failed validation {validation}"
+    )]
     Validation {
         /// Index of the variable.
         index: usize,
 
-        /// Regex that the variable value failed to match.
+        /// Regex (or other validation) that the variable value failed to match.
         validation: String,
     },
 
@@ -142,25 +1219,463 @@ pub enum Error {
     #[error("Scoped command {0} received arguments in an unexpected format")]
     InvalidInput(String),
 
-    /// A generic IO error that occurs while executing specified shell commands.
-    #[error("Scoped shell IO error: {0}")]
-    Io(#[from] std::io::Error),
-}
+    /// The arguments violate a [`CrossValidator`] of the scoped command.
+    #[error(
+        "Scoped command {command} arguments at positions {left} and {right} must satisfy {left} {relation} {right}"
+    )]
+    CrossValidation {
+        /// The name of the scoped command.
+        command: String,
+        /// The position of the argument on the left side of the relation.
+        left: usize,
+        /// The position of the argument on the right side of the relation.
+        right: usize,
+        /// The relation the values did not have.
+        relation: Relation,
+    },
 
-impl Scope {
-    /// Creates a new shell scope.
-    pub(crate) fn new<R: Runtime, M: Manager<R>>(manager: &M, mut scope: ScopeConfig) -> Self {
-        for cmd in scope.scopes.values_mut() {
-            if let Ok(path) = manager.path().parse(&cmd.command) {
-                cmd.command = path;
-            }
-        }
-        Self(scope)
-    }
+    /// The system has no default application to open the path with.
+    #[error("No default application is registered to open {0}")]
+    NoHandler(String),
 
-    /// Validates argument inputs and creates a Tauri sidecar [`Command`].
-    pub fn prepare_sidecar(
-        &self,
+    /// The launcher opening a path ran but reported a failure.
+    #[error("Launcher {launcher} failed to open the path with exit code {code:?}")]
+    LaunchFailed {
+        /// The launcher program.
+        launcher: String,
+        /// The exit code of the launcher, if it was not terminated by a signal.
+        code: Option<i32>,
+    },
+
+    /// The scoped command has a capability tag that is disabled.
+    #[error("Scoped command {command} is denied by its disabled tag {tag}")]
+    Denied {
+        /// The name of the scoped command.
+        command: String,
+        /// The disabled tag.
+        tag: String,
+    },
+
+    /// A scoped command references a group that is not configured.
+    #[error("Scoped command {command} references the unknown group {group}")]
+    UnknownGroup {
+        /// The name of the scoped command.
+        command: String,
+        /// The name of the missing group.
+        group: String,
+    },
+
+    /// A scoped command has no program, neither configured nor inherited from its group.
+    #[error("Scoped command {0} has no program to run")]
+    MissingCommand(String),
+
+    /// A scoped command was called with more arguments than it declares.
+    #[error("Scoped command {command} accepts at most {max} arguments but received {count}")]
+    TooManyArgs {
+        /// The name of the scoped command.
+        command: String,
+        /// The number of arguments received.
+        count: usize,
+        /// The number of declared argument positions.
+        max: usize,
+    },
+
+    /// Required commands are missing from the scope, see [`Scope::require_commands`].
+    #[error("The shell scope is missing the required commands {}", .0.join(", "))]
+    MissingCapabilities(Vec<String>),
+
+    /// The scope has more commands than its [`ScopeConfig::max_commands`].
+    #[error("The scope has {count} commands, more than its limit of {limit}")]
+    TooManyCommands {
+        /// The number of commands in the scope.
+        count: usize,
+        /// The maximum number of commands.
+        limit: usize,
+    },
+
+    /// The detached signature of a scope config file does not match its content.
+    #[cfg(feature = "signed-config")]
+    #[error("The signature of the scope config {} does not match its content", .0.display())]
+    InvalidSignature(PathBuf),
+
+    /// A scope config could not be parsed, or has an invalid value such as a regex that does not
+    /// compile.
+    #[error("The scope config is invalid: {0}")]
+    InvalidConfig(String),
+
+    /// The host of a URL could not be reached, see [`Scope::open_checked`].
+    #[cfg(feature = "open-checked")]
+    #[error("The URL {url} is unreachable: {reason}")]
+    Unreachable {
+        /// The URL that was not opened.
+        url: String,
+        /// Why the host could not be reached.
+        reason: String,
+    },
+
+    /// A validator regex failed to compile.
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+
+    /// The environment variable may not be set for the scoped command.
+    #[error("Scoped command {command} does not allow setting the environment variable {name}")]
+    EnvNotAllowed {
+        /// The name of the scoped command.
+        command: String,
+        /// The name of the environment variable.
+        name: String,
+    },
+
+    /// The value of an environment variable did not pass its validation.
+    #[error("Environment variable {name} failed validation {validation}")]
+    EnvValidation {
+        /// The name of the environment variable.
+        name: String,
+        /// The regex that the value failed to match.
+        validation: String,
+    },
+
+    /// The program is not one of the [`ScopeConfig::open_with`] programs.
+    #[error("Opening paths with {0} is not allowed by the shell scope")]
+    ProgramNotAllowed(String),
+
+    /// Revealing paths is not enabled in the scope.
+    #[error("Revealing {0} is not allowed by the shell scope")]
+    RevealDenied(String),
+
+    /// A revealed path did not pass the reveal validation.
+    #[error("Revealing {path} was denied, the path failed validation {validation}")]
+    RevealValidation {
+        /// The path that was not revealed.
+        path: String,
+        /// The regex that the path failed to match.
+        validation: String,
+    },
+
+    /// A path could neither be opened nor revealed, see [`Scope::open_or_reveal`].
+    #[error("The path could neither be opened ({open}) nor revealed ({reveal})")]
+    OpenOrReveal {
+        /// Why opening the path failed.
+        open: Box<Error>,
+        /// Why revealing the path failed.
+        reveal: Box<Error>,
+    },
+
+    /// A generic IO error that occurs while executing specified shell commands.
+    #[error("Scoped shell IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Merges the defaults of the command groups into the commands referencing them, and checks the
+/// consistency of the config.
+fn merge_config(mut scope: ScopeConfig) -> Result<ScopeConfig, Error> {
+    if scope.scopes.len() > scope.max_commands {
+        return Err(Error::TooManyCommands {
+            count: scope.scopes.len(),
+            limit: scope.max_commands,
+        });
+    }
+    for (name, command) in &mut scope.scopes {
+        if let Some(group) = &command.group {
+            let defaults = scope.groups.get(group).ok_or_else(|| Error::UnknownGroup {
+                command: name.clone(),
+                group: group.clone(),
+            })?;
+            defaults.apply(command);
+        }
+        if !command.sidecar && command.command.as_os_str().is_empty() {
+            return Err(Error::MissingCommand(name.clone()));
+        }
+        let positions = command.args.as_ref().map_or(0, Vec::len);
+        for validator in &command.cross_validators {
+            if let Some(position) = [validator.left, validator.right]
+                .into_iter()
+                .find(|position| *position >= positions)
+            {
+                return Err(Error::InvalidConfig(format!(
+                    "cross-argument validation of scoped command {name} references position {position}, but the command has {positions} argument positions"
+                )));
+            }
+        }
+    }
+    Ok(scope)
+}
+
+impl Scope {
+    /// Creates a new shell scope.
+    ///
+    /// The defaults of the command groups are merged into the commands referencing them.
+    pub(crate) fn new<R: Runtime, M: Manager<R>>(
+        manager: &M,
+        scope: ScopeConfig,
+    ) -> Result<Self, Error> {
+        let scope = merge_config(scope)?;
+        let children = scope
+            .scopes
+            .keys()
+            .map(|name| (name.clone(), Arc::default()))
+            .collect();
+        let scope = Self {
+            config: scope,
+            paths: Default::default(),
+            cwds: Default::default(),
+            children,
+            middlewares: Default::default(),
+            disabled_tags: Default::default(),
+            audit_sink: Default::default(),
+            audit_successes: Default::default(),
+        };
+        scope.reresolve_paths(manager)?;
+        Ok(scope)
+    }
+
+    /// Resolves the configured command paths again against the current environment.
+    ///
+    /// Command paths and working directories starting with a base directory variable (e.g.
+    /// `$RESOURCE` or `$APPDATA`) are resolved when the scope is created. If those directories change afterwards, for instance when a
+    /// portable installation is moved or the user picks another installation directory, call this so
+    /// that the commands prepared from then on use the new locations.
+    ///
+    /// Paths that fail to resolve keep their configured value, and working directories the working
+    /// directory of the app, unless [`ScopeConfig::strict_paths`] is set, in which case an
+    /// [`Error::UnresolvedPath`] is returned and the previous paths are kept.
+    pub fn reresolve_paths<R: Runtime, M: Manager<R>>(&self, manager: &M) -> Result<(), Error> {
+        self.resolve_paths_with(|path| manager.path().parse(path).ok())
+    }
+
+    /// Resolves the configured paths with `parse` like [`Self::reresolve_paths`].
+    fn resolve_paths_with(
+        &self,
+        parse: impl Fn(&std::path::Path) -> Option<PathBuf>,
+    ) -> Result<(), Error> {
+        let paths = self
+            .config
+            .scopes
+            .iter()
+            .map(|(name, cmd)| match parse(&cmd.command) {
+                Some(path) => Ok((name.clone(), path)),
+                None if self.config.strict_paths && !cmd.sidecar => Err(Error::UnresolvedPath {
+                    command: name.clone(),
+                    path: cmd.command.clone(),
+                }),
+                None => Ok((name.clone(), cmd.command.clone())),
+            })
+            .collect::<Result<_, _>>()?;
+        let cwds = self
+            .config
+            .scopes
+            .iter()
+            .filter_map(|(name, cmd)| Some((name, cmd.cwd.path()?)))
+            .filter_map(|(name, cwd)| match parse(&cwd) {
+                Some(path) => Some(Ok((name.clone(), path))),
+                None if self.config.strict_paths => Some(Err(Error::UnresolvedPath {
+                    command: name.clone(),
+                    path: cwd,
+                })),
+                // an unresolved working directory falls back to the app one
+                None => None,
+            })
+            .collect::<Result<_, _>>()?;
+        *self.paths.write().unwrap() = paths;
+        *self.cwds.write().unwrap() = cwds;
+        Ok(())
+    }
+
+    /// Adds a middleware to the end of the chain applied to the arguments of every prepared command.
+    ///
+    /// Middlewares run in the order they were added, after the arguments passed validation. Each
+    /// one receives the command name and the current arguments and returns the arguments to pass on,
+    /// or an error to reject the call. The returned arguments are not validated again.
+    pub fn add_arg_middleware(&self, middleware: ArgMiddleware) {
+        self.middlewares.write().unwrap().push(middleware);
+    }
+
+    /// Enables or disables a capability tag, all tags are enabled initially.
+    ///
+    /// A command with any disabled tag is denied with an [`Error::Denied`] error.
+    pub fn set_tag_enabled(&self, tag: &str, enabled: bool) {
+        let mut disabled = self.disabled_tags.write().unwrap();
+        if enabled {
+            disabled.remove(tag);
+        } else {
+            disabled.insert(tag.to_string());
+        }
+    }
+
+    /// Returns the names of the scoped commands with each tag, sorted by name.
+    pub fn commands_by_tag(&self) -> HashMap<String, Vec<String>> {
+        let mut by_tag: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, command) in &self.config.scopes {
+            for tag in &command.tags {
+                by_tag.entry(tag.clone()).or_default().push(name.clone());
+            }
+        }
+        for names in by_tag.values_mut() {
+            names.sort_unstable();
+        }
+        by_tag
+    }
+
+    /// Checks that the scope has a command for each of the names, e.g. at startup.
+    ///
+    /// All the missing names are returned in an [`Error::MissingCapabilities`] error, in the order
+    /// they were given. Disabled tags are not considered, a configured command is never missing.
+    pub fn require_commands(&self, names: &[&str]) -> Result<(), Error> {
+        let missing: Vec<String> = names
+            .iter()
+            .filter(|name| !self.config.scopes.contains_key(**name))
+            .map(|name| name.to_string())
+            .collect();
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::MissingCapabilities(missing))
+        }
+    }
+
+    /// Checks that every stage of a pipeline would be allowed to run at the same time, without
+    /// spawning anything.
+    ///
+    /// Each stage is validated like [`Self::validate`], and a singleton command may neither be running
+    /// already nor appear in more than one stage. The first problem found is returned.
+    pub fn pipeline_preflight(&self, stages: &[PipelineStage]) -> Result<(), Error> {
+        let mut singletons = HashSet::new();
+        for stage in stages {
+            self.validate(&stage.command, stage.args.clone(), stage.sidecar)?;
+            if self.config.scopes[&stage.command].singleton {
+                let running = self
+                    .children
+                    .get(&stage.command)
+                    .map_or(0, |tracker| tracker.running());
+                if running > 0 || !singletons.insert(stage.command.as_str()) {
+                    return Err(Error::AlreadyRunning(stage.command.clone()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the sink receiving an [`AuditEvent`] for every denied command or path, replacing the
+    /// previous one.
+    ///
+    /// Commands are audited when they are prepared, including through [`Self::spawn_interactive`]
+    /// and [`Self::spawn_to_event`], while [`Self::validate`] alone is not audited. Likewise, paths
+    /// are audited when they are opened or revealed, but not by [`Self::check_open`]. The sink is
+    /// called without any lock of the scope held, so it may use the scope.
+    pub fn set_audit_sink(&self, sink: AuditSink) {
+        self.audit_sink.write().unwrap().replace(sink);
+    }
+
+    /// Whether the audit sink also receives the allowed commands, disabled by default.
+    pub fn set_audit_successes(&self, enabled: bool) {
+        self.audit_successes.store(enabled, Ordering::SeqCst);
+    }
+
+    /// Returns the source and complexity metrics of every regex of the scope.
+    ///
+    /// This is meant for auditing patterns, e.g. for nested unbounded repetitions, and compiles each
+    /// regex several times to measure it, so it should not be called on a hot path. Commands are
+    /// reported in name order, each with its argument validators, then its environment variable
+    /// validators by variable name, then its progress regex.
+    pub fn regex_report(&self) -> Vec<RegexInfo> {
+        let mut report = Vec::new();
+        if let Some(regex) = &self.config.open {
+            report.push(RegexInfo::new(RegexLocation::Open, regex));
+        }
+        if let Some(regex) = &self.config.reveal {
+            report.push(RegexInfo::new(RegexLocation::Reveal, regex));
+        }
+        let mut names: Vec<_> = self.config.scopes.keys().collect();
+        names.sort_unstable();
+        for name in names {
+            let command = &self.config.scopes[name];
+            for (index, arg) in command.args.iter().flatten().enumerate() {
+                let regexes = match arg {
+                    ScopeAllowedArg::Var { validator, .. } => std::slice::from_ref(validator),
+                    ScopeAllowedArg::AnyOf(validators) => validators.as_slice(),
+                    _ => &[],
+                };
+                for regex in regexes {
+                    let location = RegexLocation::Argument {
+                        command: name.clone(),
+                        index,
+                    };
+                    report.push(RegexInfo::new(location, regex));
+                }
+            }
+            let mut env: Vec<_> = command.env.iter().flatten().collect();
+            env.sort_unstable_by_key(|(variable, _)| *variable);
+            for (variable, regex) in env {
+                let location = RegexLocation::Env {
+                    command: name.clone(),
+                    name: variable.clone(),
+                };
+                report.push(RegexInfo::new(location, regex));
+            }
+            if let Some(regex) = &command.progress_regex {
+                let location = RegexLocation::Progress {
+                    command: name.clone(),
+                };
+                report.push(RegexInfo::new(location, regex));
+            }
+        }
+        report
+    }
+
+    /// Returns the positions of the arguments a caller must supply to the command, in order.
+    ///
+    /// Fixed arguments are skipped, and a command accepting any arguments has no such positions.
+    /// Returns `None` if the command is not in the scope.
+    pub fn variable_positions(&self, command_name: &str) -> Option<Vec<usize>> {
+        let command = self.config.scopes.get(command_name)?;
+        Some(
+            command
+                .args
+                .iter()
+                .flatten()
+                .enumerate()
+                .filter(|(_, arg)| !arg.is_fixed())
+                .map(|(index, _)| index)
+                .collect(),
+        )
+    }
+
+    /// Returns how many scoped commands are currently running.
+    ///
+    /// A command counts as running from the moment it is spawned until its child process exits.
+    pub fn running_count(&self) -> usize {
+        self.children
+            .values()
+            .map(|tracker| tracker.running())
+            .sum()
+    }
+
+    /// Returns how many instances of each scoped command are currently running.
+    ///
+    /// Every configured command is listed, including the ones that are not running.
+    pub fn running_by_command(&self) -> HashMap<String, usize> {
+        self.children
+            .iter()
+            .map(|(name, tracker)| (name.clone(), tracker.running()))
+            .collect()
+    }
+
+    /// Terminates every running instance of a scoped command, returning how many there were.
+    ///
+    /// On Unix, the processes are asked to terminate with a `SIGTERM` and killed if they are still
+    /// running a few seconds later, elsewhere they are killed right away. Their terminated events
+    /// report a [`TerminationReason::Killed`](crate::process::TerminationReason::Killed) reason.
+    /// This does not wait for the processes to exit. An unknown command has no instances.
+    pub fn kill_by_command(&self, name: &str) -> usize {
+        self.children
+            .get(name)
+            .map(|tracker| tracker.kill_all())
+            .unwrap_or(0)
+    }
+
+    /// Validates argument inputs and creates a Tauri sidecar [`Command`].
+    pub fn prepare_sidecar(
+        &self,
         command_name: &str,
         command_script: &str,
         args: ExecuteArgs,
@@ -173,47 +1688,199 @@ impl Scope {
         self._prepare(command_name, args, None)
     }
 
-    /// Validates argument inputs and creates a Tauri [`Command`].
-    pub fn _prepare(
+    /// Validates argument inputs and environment variables, and creates a Tauri [`Command`] with
+    /// these variables set.
+    ///
+    /// Each variable must be one of the [`ScopeAllowedCommand::env`] variables, or an
+    /// [`Error::EnvNotAllowed`] error is returned, and its value must match the regex of the
+    /// variable, or an [`Error::EnvValidation`] error is returned. Allowed variables that are not
+    /// passed are not set.
+    pub fn prepare_with_env(
+        &self,
+        command_name: &str,
+        args: ExecuteArgs,
+        env: HashMap<String, String>,
+    ) -> Result<Command, Error> {
+        self.prepare_env(command_name, args, env, None)
+    }
+
+    /// Validates argument inputs and environment variables like [`Self::prepare_with_env`], and
+    /// creates a Tauri sidecar [`Command`] with these variables set.
+    pub(crate) fn prepare_sidecar_with_env(
+        &self,
+        command_name: &str,
+        command_script: &str,
+        args: ExecuteArgs,
+        env: HashMap<String, String>,
+    ) -> Result<Command, Error> {
+        self.prepare_env(command_name, args, env, Some(command_script))
+    }
+
+    /// Validates the environment variables, then prepares the command with them set.
+    fn prepare_env(
         &self,
         command_name: &str,
         args: ExecuteArgs,
+        env: HashMap<String, String>,
         sidecar: Option<&str>,
     ) -> Result<Command, Error> {
-        let command = match self.0.scopes.get(command_name) {
+        if let Err(e) = self.validate_env(command_name, &env) {
+            self.audit(command_name, &args, sidecar.is_some(), Some(&e));
+            return Err(e);
+        }
+        Ok(self._prepare(command_name, args, sidecar)?.envs(env))
+    }
+
+    /// Whether the scoped command configures the [`ScopeAllowedCommand::env`] variables callers
+    /// may set.
+    pub(crate) fn restricts_env(&self, command_name: &str) -> bool {
+        self.config
+            .scopes
+            .get(command_name)
+            .and_then(|command| command.env.as_ref())
+            .is_some()
+    }
+
+    /// Validates the environment variables passed to [`Self::prepare_with_env`].
+    fn validate_env(&self, command_name: &str, env: &HashMap<String, String>) -> Result<(), Error> {
+        if let Some(command) = self.config.scopes.get(command_name) {
+            for (name, value) in env {
+                let validator = command
+                    .env
+                    .as_ref()
+                    .and_then(|allowed| allowed.get(name))
+                    .ok_or_else(|| Error::EnvNotAllowed {
+                        command: command_name.into(),
+                        name: name.clone(),
+                    })?;
+                if !validator.is_match(value) {
+                    return Err(Error::EnvValidation {
+                        name: name.clone(),
+                        validation: validator.as_str().into(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates argument inputs without creating a [`Command`].
+    ///
+    /// Returns the arguments the command would be called with by [`Self::prepare`] (or
+    /// [`Self::prepare_sidecar`] when `sidecar` is set).
+    pub fn validate(
+        &self,
+        command_name: &str,
+        args: ExecuteArgs,
+        sidecar: bool,
+    ) -> Result<Vec<String>, Error> {
+        let command = match self.config.scopes.get(command_name) {
             Some(command) => command,
             None => return Err(Error::NotFound(command_name.into())),
         };
 
-        if command.sidecar != sidecar.is_some() {
+        if command.sidecar != sidecar {
             return Err(Error::BadSidecarFlag);
         }
 
+        let disabled = self.disabled_tags.read().unwrap();
+        if let Some(tag) = command.tags.iter().find(|tag| disabled.contains(*tag)) {
+            return Err(Error::Denied {
+                command: command_name.into(),
+                tag: tag.clone(),
+            });
+        }
+        drop(disabled);
+
         let args = match (&command.args, args) {
+            (Some(_), ExecuteArgs::Single(line))
+                if command.single_arg_mode == SingleArgMode::SplitShell =>
+            {
+                ExecuteArgs::from_shell_str(&line)?
+            }
+            (_, args) => args,
+        };
+
+        let hardening = command.hardening;
+        let cwd = self.cwds.read().unwrap().get(command_name).cloned();
+        match (&command.args, args) {
             (None, ExecuteArgs::None) => Ok(vec![]),
-            (None, ExecuteArgs::List(list)) => Ok(list),
-            (None, ExecuteArgs::Single(string)) => Ok(vec![string]),
-            (Some(list), ExecuteArgs::List(args)) => list
-                .iter()
-                .enumerate()
-                .map(|(i, arg)| match arg {
-                    ScopeAllowedArg::Fixed(fixed) => Ok(fixed.to_string()),
-                    ScopeAllowedArg::Var { validator } => {
-                        let value = args
-                            .get(i)
-                            .ok_or_else(|| Error::MissingVar(i, validator.to_string()))?
-                            .to_string();
-                        if validator.is_match(&value) {
-                            Ok(value)
-                        } else {
-                            Err(Error::Validation {
-                                index: i,
-                                validation: validator.to_string(),
-                            })
+            (None, ExecuteArgs::List(list)) => {
+                for (i, arg) in list.iter().enumerate() {
+                    hardening.check(i, arg)?;
+                }
+                Ok(list)
+            }
+            (None, ExecuteArgs::Single(string)) => {
+                hardening.check(0, &string)?;
+                Ok(vec![string])
+            }
+            // a command accepting any arguments has no named variables
+            (None, ExecuteArgs::Map(_)) => Err(Error::InvalidInput(command_name.into())),
+            (Some(list), ExecuteArgs::List(mut args)) => {
+                let passthrough = match &command.passthrough_after {
+                    // a variable value equal to the marker is not the marker
+                    Some(marker) if !command.strict_positions => {
+                        match args.iter().skip(list.len()).position(|arg| arg == marker) {
+                            Some(offset) => args.split_off(list.len() + offset),
+                            None => Vec::new(),
                         }
                     }
-                })
-                .collect(),
+                    _ => Vec::new(),
+                };
+                if (command.strict_positions || hardening.reject_extra_args)
+                    && args.len() > list.len()
+                {
+                    return Err(Error::TooManyArgs {
+                        command: command_name.into(),
+                        count: args.len(),
+                        max: list.len(),
+                    });
+                }
+                let mut argv = Vec::new();
+                for (i, arg) in list.iter().enumerate() {
+                    if let (false, Some(value)) = (arg.is_fixed(), args.get(i)) {
+                        hardening.check(i, value)?;
+                    }
+                    arg.resolve(i, args.get(i), cwd.as_deref(), &mut argv)?;
+                }
+                if let Some(offset) = passthrough
+                    .iter()
+                    .position(|arg| arg.chars().any(char::is_control))
+                {
+                    return Err(Error::Validation {
+                        index: args.len() + offset,
+                        validation: "no control characters".into(),
+                    });
+                }
+                cross_validate(command_name, command, list, &args)?;
+                argv.extend(passthrough);
+                Ok(argv)
+            }
+            (Some(list), ExecuteArgs::Map(map)) => {
+                let is_named = |name: &String| list.iter().any(|arg| arg.name() == Some(name));
+                if list.iter().all(|arg| arg.name().is_none()) || !map.keys().all(is_named) {
+                    return Err(Error::InvalidInput(command_name.into()));
+                }
+                // the values by position, for the cross-argument validation
+                let mut args = Vec::with_capacity(list.len());
+                let mut argv = Vec::new();
+                for (i, arg) in list.iter().enumerate() {
+                    let value = match (arg, arg.name()) {
+                        (ScopeAllowedArg::Fixed(_), _) => None,
+                        (_, Some(name)) => map.get(name),
+                        // positional variables cannot be passed by name
+                        (_, None) => return Err(Error::InvalidInput(command_name.into())),
+                    };
+                    if let Some(value) = value {
+                        hardening.check(i, value)?;
+                    }
+                    arg.resolve(i, value, cwd.as_deref(), &mut argv)?;
+                    args.push(value.cloned().unwrap_or_default());
+                }
+                cross_validate(command_name, command, list, &args)?;
+                Ok(argv)
+            }
             (Some(list), arg) if arg.is_empty() && list.iter().all(ScopeAllowedArg::is_fixed) => {
                 list.iter()
                     .map(|arg| match arg {
@@ -224,7 +1891,146 @@ impl Scope {
             }
             (Some(list), _) if list.is_empty() => Err(Error::InvalidInput(command_name.into())),
             (Some(_), _) => Err(Error::InvalidInput(command_name.into())),
-        }?;
+        }
+    }
+
+    /// Computes the environment the scoped command would run with, without spawning it.
+    ///
+    /// This is the environment a [`Command`] prepared with [`Self::prepare_with_env`] and
+    /// `caller_env` receives: the environment of the current process overlaid with `caller_env`,
+    /// which is validated the same way, without the variables the command removes.
+    /// The values of variables that look like secrets (e.g. `API_TOKEN`) are masked.
+    pub fn resolve_env(
+        &self,
+        command_name: &str,
+        caller_env: HashMap<String, String>,
+    ) -> Result<HashMap<String, String>, Error> {
+        let scoped = self
+            .config
+            .scopes
+            .get(command_name)
+            .ok_or_else(|| Error::NotFound(command_name.into()))?;
+        self.validate_env(command_name, &caller_env)?;
+
+        let command = scoped_env(scoped, Command::new(String::new()).envs(caller_env));
+        let mut env = command.resolved_env();
+        for (name, value) in env.iter_mut() {
+            if is_secret_env(name) {
+                *value = "***".into();
+            }
+        }
+        Ok(env)
+    }
+
+    /// Validates argument inputs and creates a Tauri [`Command`].
+    ///
+    /// Denied commands are reported to the audit sink, see [`Self::set_audit_sink`].
+    pub fn _prepare(
+        &self,
+        command_name: &str,
+        args: ExecuteArgs,
+        sidecar: Option<&str>,
+    ) -> Result<Command, Error> {
+        let audited_args = self
+            .audit_sink
+            .read()
+            .unwrap()
+            .is_some()
+            .then(|| args.clone());
+        let result = self.prepare_command(command_name, args, sidecar);
+        if let Some(args) = audited_args {
+            self.audit(
+                command_name,
+                &args,
+                sidecar.is_some(),
+                result.as_ref().err(),
+            );
+        }
+        result
+    }
+
+    /// Reports a command to the audit sink, if it was denied with `error` or successes are audited.
+    fn audit(&self, command_name: &str, args: &ExecuteArgs, sidecar: bool, error: Option<&Error>) {
+        let args = || match args {
+            ExecuteArgs::None => Vec::new(),
+            ExecuteArgs::Single(arg) => vec![arg.clone()],
+            ExecuteArgs::List(args) => args.clone(),
+            ExecuteArgs::Map(map) => {
+                let mut args: Vec<_> = map
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect();
+                args.sort_unstable();
+                args
+            }
+        };
+        self.record(AuditAction::Execute, command_name, args, sidecar, error);
+    }
+
+    /// Reports an opened or revealed path to the audit sink, like [`Self::audit`].
+    fn audit_path(
+        &self,
+        action: AuditAction,
+        path: &str,
+        with: Option<Program>,
+        error: Option<&Error>,
+    ) {
+        let program = with.map_or("", Program::name);
+        self.record(action, program, || vec![path.into()], false, error);
+    }
+
+    /// Sends an event to the audit sink, unless it is a success and successes are not audited.
+    fn record(
+        &self,
+        action: AuditAction,
+        command: &str,
+        args: impl FnOnce() -> Vec<String>,
+        sidecar: bool,
+        error: Option<&Error>,
+    ) {
+        let outcome = match error {
+            None if !self.audit_successes.load(Ordering::SeqCst) => return,
+            None => AuditOutcome::Allowed,
+            Some(e) => AuditOutcome::Denied(e.to_string()),
+        };
+        // the lock is released before calling the sink, which may use the scope
+        let sink = self.audit_sink.read().unwrap().clone();
+        if let Some(sink) = sink {
+            sink(AuditEvent {
+                action,
+                command: command.into(),
+                args: args(),
+                sidecar,
+                outcome,
+                timestamp: SystemTime::now(),
+            });
+        }
+    }
+
+    /// Validates argument inputs and creates a Tauri [`Command`], without auditing it.
+    fn prepare_command(
+        &self,
+        command_name: &str,
+        args: ExecuteArgs,
+        sidecar: Option<&str>,
+    ) -> Result<Command, Error> {
+        let args = self.middlewares.read().unwrap().iter().try_fold(
+            self.validate(command_name, args, sidecar.is_some())?,
+            |args, middleware| middleware(command_name, args),
+        )?;
+        let scoped = &self.config.scopes[command_name];
+
+        let tracking = self.children.get(command_name).map(|tracker| Tracking {
+            name: command_name.into(),
+            tracker: tracker.clone(),
+            singleton: scoped.singleton,
+        });
+        // checked again when spawning, since another instance can be spawned in between
+        if let Some(tracking) = tracking.as_ref().filter(|tracking| tracking.singleton) {
+            if tracking.tracker.running() > 0 {
+                return Err(Error::AlreadyRunning(command_name.into()));
+            }
+        }
 
         let command_s = sidecar
             .map(|s| {
@@ -236,37 +2042,2061 @@ impl Scope {
                     .to_string_lossy()
                     .into_owned()
             })
-            .unwrap_or_else(|| command.command.to_string_lossy().into_owned());
-        let command = if command.sidecar {
+            .unwrap_or_else(|| {
+                self.paths
+                    .read()
+                    .unwrap()
+                    .get(command_name)
+                    .unwrap_or(&scoped.command)
+                    .to_string_lossy()
+                    .into_owned()
+            });
+        let mut command = if scoped.sidecar {
             Command::new_sidecar(command_s).map_err(|e| Error::Sidecar(e.to_string()))?
         } else {
             Command::new(command_s)
         };
 
-        Ok(command.args(args))
+        if let Some(tracking) = tracking {
+            command = command.tracked(tracking);
+        }
+        if let Some(priority) = scoped.io_priority {
+            command = command.io_priority(priority);
+        }
+        if let Some(cwd) = self.cwds.read().unwrap().get(command_name) {
+            command = command.current_dir(cwd.clone());
+        }
+        if let Some(regex) = &scoped.progress_regex {
+            command = command.progress_regex(regex.clone());
+        }
+        command = scoped_env(scoped, command);
+
+        Ok(command
+            .args(args)
+            .policy(scoped.policy.clone().unwrap_or_default()))
+    }
+
+    /// Validates argument inputs and spawns the command as an interactive session.
+    ///
+    /// The returned [`InteractiveChild`] streams the process output and keeps its stdin open until
+    /// [`InteractiveChild::close_stdin`] is called, which suits REPL-like programs such as database shells.
+    /// Only commands configured with `allowStdin` can be spawned this way.
+    pub fn spawn_interactive(
+        &self,
+        command_name: &str,
+        args: ExecuteArgs,
+    ) -> crate::Result<InteractiveChild> {
+        let denied = match self.config.scopes.get(command_name) {
+            Some(command) if command.allow_stdin => None,
+            Some(_) => Some(Error::StdinNotAllowed(command_name.into())),
+            None => Some(Error::NotFound(command_name.into())),
+        };
+        if let Some(error) = denied {
+            self.audit(command_name, &args, false, Some(&error));
+            return Err(error.into());
+        }
+
+        let (rx, child) = self.prepare(command_name, args)?.spawn()?;
+        Ok(InteractiveChild::new(rx, child))
+    }
+
+    /// Validates argument inputs, spawns the command and emits its [`CommandEvent`]s as `event`.
+    ///
+    /// The events are emitted to every window, so the frontend only has to listen to `event`.
+    /// Output lines are emitted as raw bytes. The returned child can be used to kill the process.
+    pub fn spawn_to_event<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        event: &str,
+        command_name: &str,
+        args: ExecuteArgs,
+    ) -> crate::Result<CommandChild> {
+        let (rx, child) = self.prepare(command_name, args)?.spawn()?;
+
+        let app = app.clone();
+        let event = event.to_string();
+        tauri::async_runtime::spawn(forward_events(rx, move |payload| {
+            let _ = app.emit_all(&event, payload);
+        }));
+
+        Ok(child)
     }
 
     /// Open a path in the default (or specified) browser.
     ///
     /// The path is validated against the `plugins > shell > open` validation regex, which
     /// defaults to `^((mailto:\w+)|(tel:\w+)|(https?://\w+)).+`.
+    ///
+    /// If no program is specified and the system has no default application for the path, the path
+    /// is opened with the configured default program instead, or an [`Error::NoHandler`] is returned.
+    /// The default program must be allowed by [`ScopeConfig::open_with`] like any other, or the
+    /// open is denied with an [`Error::ProgramNotAllowed`].
+    ///
+    /// A launcher reporting a failure is an [`Error::LaunchFailed`], see [`Self::open_status`].
     pub fn open(&self, path: &str, with: Option<Program>) -> Result<(), Error> {
-        // ensure we pass validation if the configuration has one
-        if let Some(regex) = &self.0.open {
-            if !regex.is_match(path) {
-                return Err(Error::Validation {
-                    index: 0,
-                    validation: regex.as_str().into(),
-                });
+        self.open_status(path, with)?.into_result()
+    }
+
+    /// Reveal a path in the file manager, selecting it where the platform supports it.
+    ///
+    /// The path is validated against the `plugins > shell > reveal` validation regex, failing with
+    /// an [`Error::RevealValidation`] error, and revealing is denied with an [`Error::RevealDenied`]
+    /// error if it is not configured. On Linux, the parent directory is opened instead when no file
+    /// manager can select the path.
+    pub fn reveal(&self, path: &str) -> Result<(), Error> {
+        let checked = self.check_reveal(path);
+        self.audit_path(AuditAction::Reveal, path, None, checked.as_ref().err());
+        checked?;
+        let path = std::path::Path::new(path);
+        std::fs::metadata(path)?;
+        reveal_path(path)
+    }
+
+    /// Checks that the path may be revealed, without revealing it.
+    fn check_reveal(&self, path: &str) -> Result<(), Error> {
+        match &self.config.reveal {
+            None => Err(Error::RevealDenied(path.into())),
+            Some(regex) if !regex.is_match(path) => Err(Error::RevealValidation {
+                path: path.into(),
+                validation: regex.as_str().into(),
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Open a path with its default application, or reveal it in the file manager if that fails.
+    ///
+    /// The path is revealed when the system has no application for it or the launcher fails, and
+    /// must pass the validations of both [`Self::open`] and [`Self::reveal`] in that case. If
+    /// revealing fails too, both errors are returned in an [`Error::OpenOrReveal`] error.
+    pub fn open_or_reveal(&self, path: &str) -> Result<(), Error> {
+        self.reveal_on_failure(path, self.open(path, None))
+    }
+
+    /// Reveals the path if opening it failed in a way revealing can recover from.
+    fn reveal_on_failure(&self, path: &str, opened: Result<(), Error>) -> Result<(), Error> {
+        match opened {
+            Err(open @ (Error::NoHandler(_) | Error::LaunchFailed { .. } | Error::Io(_))) => {
+                self.reveal(path).map_err(|reveal| Error::OpenOrReveal {
+                    open: Box::new(open),
+                    reveal: Box::new(reveal),
+                })
             }
+            result => result,
         }
+    }
+
+    /// Open a path like [`Self::open`], returning the status the launcher reported.
+    ///
+    /// Launchers that could not be spawned at all are still errors, while a launcher that ran but
+    /// failed, e.g. because no application is associated with the path, is an [`OpenStatus::Failed`]
+    /// unless the configured default program opens the path instead.
+    pub fn open_status(&self, path: &str, with: Option<Program>) -> Result<OpenStatus, Error> {
+        let checked = self.check_open(path, with);
+        self.audit_path(AuditAction::Open, path, with, checked.as_ref().err());
+        checked?;
 
         // The prevention of argument escaping is handled by the usage of std::process::Command::arg by
         // the `open` dependency. This behavior should be re-confirmed during upgrades of `open`.
         match with.map(Program::name) {
-            Some(program) => ::open::with(path, program),
-            None => ::open::that(path),
+            Some(program) => Ok(launch(::open::with_command(path, program))?),
+            None => self.launch_default(path, ::open::commands(path), |program| {
+                ::open::with_command(path, program)
+            }),
+        }
+    }
+
+    /// Runs the `launchers` in order until one opens the path, like `open::that`, falling back to
+    /// the [`ScopeConfig::open_default_program`] launched by `fallback`.
+    ///
+    /// The fallback is used when no launcher could be spawned, or when all the launchers that ran
+    /// reported a failure, e.g. an `xdg-open` without any handler. Without a default program, the
+    /// first launcher failure is returned.
+    fn launch_default(
+        &self,
+        path: &str,
+        launchers: Vec<std::process::Command>,
+        fallback: impl FnOnce(&str) -> std::process::Command,
+    ) -> Result<OpenStatus, Error> {
+        let mut failed = None;
+        let mut last_err = None;
+        for command in launchers {
+            match launch(command) {
+                Ok(status @ OpenStatus::Launched { .. }) => return Ok(status),
+                Ok(status) => {
+                    failed.get_or_insert(status);
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        match (failed, last_err) {
+            (Some(status), _) if self.config.open_default_program.is_none() => return Ok(status),
+            // no launcher could be spawned at all
+            (None, Some(e)) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
         }
-        .map_err(Into::into)
+        let program = self.fallback_program(path);
+        if let Err(e) = &program {
+            let program = self.config.open_default_program;
+            self.audit_path(AuditAction::Open, path, program, Some(e));
+        }
+        Ok(launch(fallback(program?.name()))?)
+    }
+
+    /// The program opening the path when the system has no default application for it.
+    ///
+    /// The [`ScopeConfig::open_default_program`] is subject to [`ScopeConfig::open_with`] like
+    /// the programs chosen by the caller, and an [`Error::NoHandler`] is returned without one.
+    fn fallback_program(&self, path: &str) -> Result<Program, Error> {
+        let program = self
+            .config
+            .open_default_program
+            .ok_or_else(|| Error::NoHandler(path.into()))?;
+        match &self.config.open_with {
+            Some(allowed) if !allowed.iter().any(|name| name == program.name()) => {
+                Err(Error::ProgramNotAllowed(program.name().into()))
+            }
+            _ => Ok(program),
+        }
+    }
+
+    /// Checks that the path may be opened with the program, without opening it.
+    ///
+    /// The path must pass the open validation if the configuration has one, and the program must
+    /// be one of the [`ScopeConfig::open_with`] programs, or an [`Error::ProgramNotAllowed`] error is
+    /// returned.
+    pub fn check_open(&self, path: &str, with: Option<Program>) -> Result<(), Error> {
+        if let Some(regex) = &self.config.open {
+            if !regex.is_match(path) {
+                return Err(Error::Validation {
+                    index: 0,
+                    validation: regex.as_str().into(),
+                });
+            }
+        }
+        match (with.map(Program::name), &self.config.open_with) {
+            (Some(program), Some(allowed)) if !allowed.iter().any(|name| name == program) => {
+                Err(Error::ProgramNotAllowed(program.into()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Open a URL like [`Self::open`], but only once its host is reachable.
+    ///
+    /// The URL is validated first, then its host must resolve to an address, and when a
+    /// `head_timeout` is set, an `http` or `https` URL must also answer a `HEAD` request within it.
+    /// Any response counts as reachable, including error statuses. An unreachable URL is an
+    /// [`Error::Unreachable`] error. Paths without a host, like files or `mailto:` URLs, are opened
+    /// without a check.
+    #[cfg(feature = "open-checked")]
+    pub fn open_checked(
+        &self,
+        url: &str,
+        with: Option<Program>,
+        head_timeout: Option<std::time::Duration>,
+    ) -> Result<(), Error> {
+        // an allowed URL is audited when it is opened
+        if let Err(e) = self.check_open(url, with) {
+            self.audit_path(AuditAction::Open, url, with, Some(&e));
+            return Err(e);
+        }
+        check_reachable(url, head_timeout)?;
+        self.open(url, with)
+    }
+}
+
+/// Checks that the host of a URL is reachable, see [`Scope::open_checked`].
+#[cfg(feature = "open-checked")]
+fn check_reachable(url: &str, head_timeout: Option<std::time::Duration>) -> Result<(), Error> {
+    let unreachable = |reason: String| Error::Unreachable {
+        url: url.into(),
+        reason,
+    };
+    let Some(parsed) = url::Url::parse(url).ok().filter(|u| u.host().is_some()) else {
+        return Ok(());
+    };
+    // `socket_addrs` strips the brackets of IPv6 hosts, and an empty resolution is as unreachable
+    // as a failed one
+    let resolved = parsed
+        .socket_addrs(|| Some(0))
+        .map_err(|e| unreachable(e.to_string()))?;
+    if resolved.is_empty() {
+        let host = parsed.host_str().unwrap_or_default();
+        return Err(unreachable(format!("{host} has no address")));
+    }
+    if let (Some(timeout), "http" | "https") = (head_timeout, parsed.scheme()) {
+        match ureq::head(url).timeout(timeout).call() {
+            Ok(_) | Err(ureq::Error::Status(..)) => {}
+            Err(e) => return Err(unreachable(e.to_string())),
+        }
+    }
+    Ok(())
+}
+
+/// Passes each event of the command to `emit`, until the command terminated.
+async fn forward_events(
+    mut rx: tauri::async_runtime::Receiver<CommandEvent>,
+    emit: impl Fn(CommandEvent),
+) {
+    while let Some(payload) = rx.recv().await {
+        emit(payload);
+    }
+}
+
+/// Checks the argument values against the cross-argument validations of the scoped command.
+fn cross_validate(
+    command_name: &str,
+    command: &ScopeAllowedCommand,
+    list: &[ScopeAllowedArg],
+    args: &[String],
+) -> Result<(), Error> {
+    match command
+        .cross_validators
+        .iter()
+        .find(|validator| !validator.check(list, args))
+    {
+        Some(validator) => Err(Error::CrossValidation {
+            command: command_name.into(),
+            left: validator.left,
+            right: validator.right,
+            relation: validator.relation,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Applies the environment settings of the scoped command to the command.
+fn scoped_env(scoped: &ScopeAllowedCommand, mut command: Command) -> Command {
+    if scoped.hardening.strip_dangerous_env {
+        for key in DANGEROUS_ENV {
+            command = command.env_remove(*key);
+        }
+    }
+    command
+}
+
+/// A sample invocation checked by [`Scope::check_cases`].
+#[cfg(feature = "policy-testing")]
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TestCase {
+    /// The name of the scoped command to call.
+    pub command: String,
+
+    /// The arguments the command is called with.
+    #[serde(default = "default_case_args")]
+    pub args: ExecuteArgs,
+
+    /// If the command is called as a sidecar.
+    #[serde(default)]
+    pub sidecar: bool,
+
+    /// The outcome the scope validation must have.
+    pub expected: CaseExpectation,
+}
+
+#[cfg(feature = "policy-testing")]
+fn default_case_args() -> ExecuteArgs {
+    ExecuteArgs::None
+}
+
+/// The expected outcome of a [`TestCase`].
+#[cfg(feature = "policy-testing")]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CaseExpectation {
+    /// The invocation passes validation.
+    Allow,
+
+    /// The invocation fails validation, with any error.
+    Deny,
+
+    /// The invocation fails validation with an error displayed as the given message.
+    Error(String),
+}
+
+/// A [`TestCase`] that did not have its expected outcome.
+#[cfg(feature = "policy-testing")]
+#[derive(Debug, Clone)]
+pub struct CaseFailure {
+    /// Index of the case in the checked list.
+    pub index: usize,
+
+    /// The outcome the case expected.
+    pub expected: CaseExpectation,
+
+    /// The error the validation returned, if any.
+    pub error: Option<String>,
+}
+
+/// The result of [`Scope::check_cases`].
+#[cfg(feature = "policy-testing")]
+#[derive(Debug, Clone, Default)]
+pub struct CaseReport {
+    /// How many cases had their expected outcome.
+    pub passed: usize,
+
+    /// The cases that did not have their expected outcome.
+    pub failures: Vec<CaseFailure>,
+}
+
+#[cfg(feature = "policy-testing")]
+impl CaseReport {
+    /// Whether every case had its expected outcome.
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[cfg(feature = "policy-testing")]
+impl Scope {
+    /// Validates each sample invocation and compares the outcome against the expected one.
+    ///
+    /// Nothing is spawned, the cases only go through [`Self::validate`]. Keeping a table of cases
+    /// next to the scope configuration catches changes that accidentally loosen or tighten it.
+    pub fn check_cases(&self, cases: &[TestCase]) -> CaseReport {
+        let mut report = CaseReport::default();
+        for (index, case) in cases.iter().enumerate() {
+            let error = self
+                .validate(&case.command, case.args.clone(), case.sidecar)
+                .err()
+                .map(|e| e.to_string());
+            let passed = match (&case.expected, &error) {
+                (CaseExpectation::Allow, None) => true,
+                (CaseExpectation::Deny, Some(_)) => true,
+                (CaseExpectation::Error(expected), Some(error)) => expected == error,
+                _ => false,
+            };
+            if passed {
+                report.passed += 1;
+            } else {
+                report.failures.push(CaseFailure {
+                    index,
+                    expected: case.expected.clone(),
+                    error,
+                });
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(command: ScopeAllowedCommand) -> Scope {
+        let mut scopes = HashMap::new();
+        scopes.insert("cmd".to_string(), command);
+        Scope {
+            config: ScopeConfig {
+                open: None,
+                reveal: None,
+                open_default_program: None,
+                open_with: None,
+                scopes,
+                strict_paths: false,
+                max_commands: DEFAULT_MAX_COMMANDS,
+                groups: HashMap::new(),
+            },
+            paths: Default::default(),
+            cwds: Default::default(),
+            children: [("cmd".to_string(), Arc::default())].into_iter().collect(),
+            middlewares: Default::default(),
+            disabled_tags: Default::default(),
+            audit_sink: Default::default(),
+            audit_successes: Default::default(),
+        }
+    }
+
+    /// A command running `program` with any arguments and every option left to its default.
+    fn command(program: &str) -> ScopeAllowedCommand {
+        ScopeAllowedCommand {
+            command: program.into(),
+            args: None,
+            sidecar: false,
+            allow_stdin: false,
+            singleton: false,
+            policy: Default::default(),
+            io_priority: None,
+            group: None,
+            tags: Vec::new(),
+            cwd: Default::default(),
+            cross_validators: Vec::new(),
+            single_arg_mode: Default::default(),
+            passthrough_after: None,
+            progress_regex: None,
+            strict_positions: false,
+            env: None,
+            hardening: Default::default(),
+        }
+    }
+
+    fn strict_command() -> ScopeAllowedCommand {
+        ScopeAllowedCommand {
+            args: Some(vec![
+                ScopeAllowedArg::Fixed("-v".into()),
+                ScopeAllowedArg::var_str(r"^\w+$").unwrap(),
+            ]),
+            passthrough_after: Some("--".into()),
+            strict_positions: true,
+            ..command("cmd")
+        }
+    }
+
+    /// A scope with a `cmd` command taking the single argument `arg`.
+    fn arg_scope(arg: ScopeAllowedArg) -> Scope {
+        scope(ScopeAllowedCommand {
+            args: Some(vec![arg]),
+            ..command("cmd")
+        })
+    }
+
+    fn list(args: &[&str]) -> ExecuteArgs {
+        ExecuteArgs::List(args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    #[test]
+    fn test_strict_positions_exact_supply() {
+        let scope = scope(strict_command());
+        let argv = scope
+            .validate("cmd", list(&["-v", "value"]), false)
+            .unwrap();
+        assert_eq!(argv, ["-v", "value"]);
+    }
+
+    #[test]
+    fn test_strict_positions_over_supply() {
+        let scope = scope(strict_command());
+        let err = scope
+            .validate("cmd", list(&["-v", "value", "extra"]), false)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::TooManyArgs {
+                count: 3,
+                max: 2,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_strict_positions_ignore_passthrough() {
+        let scope = scope(strict_command());
+        let err = scope
+            .validate("cmd", list(&["-v", "value", "--", "extra"]), false)
+            .unwrap_err();
+        assert!(matches!(err, Error::TooManyArgs { count: 4, .. }));
+    }
+
+    #[test]
+    fn test_strict_positions_under_supply() {
+        let scope = scope(strict_command());
+        let err = scope.validate("cmd", list(&["-v"]), false).unwrap_err();
+        assert!(matches!(err, Error::MissingVar(1, _)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_kill_by_command() {
+        use crate::process::TerminationReason;
+
+        let scope = scope(command("sleep"));
+        let (mut rx, _child) = scope
+            .prepare("cmd", list(&["10"]))
+            .unwrap()
+            .spawn()
+            .unwrap();
+
+        assert_eq!(scope.kill_by_command("cmd"), 1);
+        assert_eq!(scope.kill_by_command("other"), 0);
+        let mut terminated = None;
+        tauri::async_runtime::block_on(async {
+            while let Some(event) = rx.recv().await {
+                if let CommandEvent::Terminated(payload) = event {
+                    terminated = Some(payload);
+                }
+            }
+        });
+        let terminated = terminated.unwrap();
+        assert_eq!(terminated.reason, TerminationReason::Killed);
+        assert_eq!(terminated.signal, Some(libc::SIGTERM));
+        assert_eq!(scope.kill_by_command("cmd"), 0);
+    }
+
+    #[test]
+    fn test_group_defaults_policy() {
+        let defaults = CommandGroupDefaults {
+            policy: Some(ExecutionPolicy {
+                retries: 2,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let mut inheriting = command("cmd");
+        defaults.apply(&mut inheriting);
+        assert_eq!(inheriting.policy, defaults.policy);
+
+        // a policy equal to the default one is still the command's own
+        let mut overriding = ScopeAllowedCommand {
+            policy: Some(ExecutionPolicy::default()),
+            ..command("cmd")
+        };
+        defaults.apply(&mut overriding);
+        assert_eq!(overriding.policy, Some(ExecutionPolicy::default()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_running_from_spawn() {
+        let scope = scope(ScopeAllowedCommand {
+            singleton: true,
+            ..command("sleep")
+        });
+
+        // prepared commands do not count as running, nor block the singleton
+        let first = scope.prepare("cmd", list(&["10"])).unwrap();
+        let second = scope.prepare("cmd", list(&["10"])).unwrap();
+        assert_eq!(scope.running_count(), 0);
+
+        let (_rx, child) = first.spawn().unwrap();
+        assert_eq!(scope.running_count(), 1);
+        let again = scope.prepare("cmd", list(&["10"]));
+        assert!(matches!(again, Err(Error::AlreadyRunning(_))));
+        let spawned = second.spawn();
+        assert!(matches!(
+            spawned,
+            Err(crate::Error::Scope(Error::AlreadyRunning(_)))
+        ));
+        assert_eq!(scope.running_count(), 1);
+        child.kill().unwrap();
+    }
+
+    fn named_command() -> ScopeAllowedCommand {
+        let named = |name: &str| match ScopeAllowedArg::var_str(r"^\w+$").unwrap() {
+            ScopeAllowedArg::Var { validator, .. } => ScopeAllowedArg::Var {
+                validator,
+                max_len: None,
+                require_captures: Vec::new(),
+                to_native_path: false,
+                name: Some(name.into()),
+            },
+            _ => unreachable!(),
+        };
+        ScopeAllowedCommand {
+            args: Some(vec![
+                named("input"),
+                ScopeAllowedArg::Fixed("-o".into()),
+                named("output"),
+            ]),
+            ..command("cmd")
+        }
+    }
+
+    fn map(args: &[(&str, &str)]) -> ExecuteArgs {
+        ExecuteArgs::Map(
+            args.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_named_vars() {
+        let scope = scope(named_command());
+        let args = map(&[("output", "out"), ("input", "in")]);
+        let argv = scope.validate("cmd", args, false).unwrap();
+        assert_eq!(argv, ["in", "-o", "out"]);
+
+        // named variables are still positional in a list
+        let argv = scope
+            .validate("cmd", list(&["in", "-o", "out"]), false)
+            .unwrap();
+        assert_eq!(argv, ["in", "-o", "out"]);
+    }
+
+    #[test]
+    fn test_named_vars_invalid() {
+        let positional = scope(strict_command());
+        let scope = scope(named_command());
+        let missing = scope.validate("cmd", map(&[("input", "in")]), false);
+        assert!(matches!(missing, Err(Error::MissingVar(2, _))));
+
+        let failing = scope.validate("cmd", map(&[("input", "in"), ("output", "a b")]), false);
+        assert!(matches!(failing, Err(Error::Validation { index: 2, .. })));
+
+        let unknown = map(&[("input", "in"), ("output", "out"), ("other", "x")]);
+        let unknown = scope.validate("cmd", unknown, false);
+        assert!(matches!(unknown, Err(Error::InvalidInput(_))));
+
+        let positional = positional.validate("cmd", map(&[("input", "in")]), false);
+        assert!(matches!(positional, Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    fn test_open_with() {
+        let mut scope = scope(command("cmd"));
+        scope.config.open_with = Some(vec![Program::Firefox.name().into()]);
+
+        assert!(scope.check_open("https://tauri.app", None).is_ok());
+        assert!(scope
+            .check_open("https://tauri.app", Some(Program::Firefox))
+            .is_ok());
+        let denied = scope.check_open("https://tauri.app", Some(Program::Chrome));
+        assert!(matches!(denied, Err(Error::ProgramNotAllowed(_))));
+
+        scope.config.open_with = Some(Vec::new());
+        assert!(scope.check_open("https://tauri.app", None).is_ok());
+        let denied = scope.check_open("https://tauri.app", Some(Program::Firefox));
+        assert!(matches!(denied, Err(Error::ProgramNotAllowed(_))));
+
+        scope.config.open_with = None;
+        assert!(scope
+            .check_open("https://tauri.app", Some(Program::Chrome))
+            .is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_launch_default() {
+        let mut scope = scope(command("cmd"));
+        let path = "https://tauri.app";
+        let failing = || {
+            let mut command = std::process::Command::new("sh");
+            command.args(["-c", "exit 4"]);
+            command
+        };
+        let missing = || std::process::Command::new("tauri-plugin-shell-missing");
+        let fallback = |program: &str| {
+            assert_eq!(program, Program::Firefox.name());
+            std::process::Command::new("true")
+        };
+
+        // without a default program, the first failure is returned
+        let status = scope.launch_default(path, vec![missing(), failing()], fallback);
+        assert!(matches!(
+            status,
+            Ok(OpenStatus::Failed { code: Some(4), .. })
+        ));
+        assert!(matches!(
+            scope.launch_default(path, vec![missing()], fallback),
+            Err(Error::NoHandler(_))
+        ));
+        // a later launcher may still open the path
+        let launched = scope.launch_default(
+            path,
+            vec![failing(), std::process::Command::new("true")],
+            fallback,
+        );
+        assert!(matches!(launched, Ok(OpenStatus::Launched { launcher }) if launcher == "true"));
+
+        // the launcher ran but had no handler
+        scope.config.open_default_program = Some(Program::Firefox);
+        let status = scope.launch_default(path, vec![failing(), failing()], fallback);
+        assert!(matches!(status, Ok(OpenStatus::Launched { launcher }) if launcher == "true"));
+        let status = scope.launch_default(path, vec![missing()], fallback);
+        assert!(matches!(status, Ok(OpenStatus::Launched { .. })));
+
+        scope.config.open_with = Some(Vec::new());
+        assert!(matches!(
+            scope.launch_default(path, vec![failing()], fallback),
+            Err(Error::ProgramNotAllowed(_))
+        ));
+    }
+
+    #[test]
+    fn test_fallback_program() {
+        let mut scope = scope(command("cmd"));
+        let path = "https://tauri.app";
+        assert!(matches!(
+            scope.fallback_program(path),
+            Err(Error::NoHandler(_))
+        ));
+
+        scope.config.open_default_program = Some(Program::Firefox);
+        assert_eq!(scope.fallback_program(path).unwrap(), Program::Firefox);
+        // the fallback obeys the allowlist like the programs of the caller
+        scope.config.open_with = Some(vec![Program::Chrome.name().into()]);
+        assert!(matches!(
+            scope.fallback_program(path),
+            Err(Error::ProgramNotAllowed(program)) if program == Program::Firefox.name()
+        ));
+        scope.config.open_with = Some(vec![Program::Firefox.name().into()]);
+        assert_eq!(scope.fallback_program(path).unwrap(), Program::Firefox);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_prepare_with_env() {
+        let env = |vars: &[(&str, &str)]| -> HashMap<String, String> {
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let args = || list(&["-c", "echo \"$LEVEL:$MODE\""]);
+        let with_env = scope(ScopeAllowedCommand {
+            env: Some(
+                [
+                    ("LEVEL".to_string(), Regex::new(r"^\d$").unwrap()),
+                    ("MODE".to_string(), Regex::new(r"^\w+$").unwrap()),
+                ]
+                .into_iter()
+                .collect(),
+            ),
+            ..command("sh")
+        });
+
+        // an allowed variable that is not passed is not set
+        let prepared = with_env
+            .prepare_with_env("cmd", args(), env(&[("LEVEL", "3")]))
+            .unwrap();
+        let output = tauri::async_runtime::block_on(prepared.output()).unwrap();
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "3:\n");
+
+        let failing = with_env.prepare_with_env("cmd", args(), env(&[("LEVEL", "high")]));
+        assert!(matches!(failing, Err(Error::EnvValidation { .. })));
+        let unlisted = with_env.prepare_with_env("cmd", args(), env(&[("PATH", "/tmp")]));
+        assert!(matches!(unlisted, Err(Error::EnvNotAllowed { .. })));
+
+        let without_env = scope(command("sh"));
+        let denied = without_env.prepare_with_env("cmd", args(), env(&[("LEVEL", "3")]));
+        assert!(matches!(denied, Err(Error::EnvNotAllowed { .. })));
+        assert!(without_env
+            .prepare_with_env("cmd", args(), env(&[]))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_restricts_env() {
+        let mut scope = scope(command("cmd"));
+        scope.config.scopes.insert(
+            "env".into(),
+            ScopeAllowedCommand {
+                env: Some([("LEVEL".to_string(), Regex::new(r"^\d$").unwrap())].into()),
+                sidecar: true,
+                ..command("")
+            },
+        );
+        assert!(!scope.restricts_env("cmd"));
+        assert!(scope.restricts_env("env"));
+        assert!(!scope.restricts_env("missing"));
+
+        let env = |value: &str| [("LEVEL".to_string(), value.to_string())].into();
+        assert!(scope
+            .prepare_sidecar_with_env("env", "bin/env", list(&[]), env("3"))
+            .is_ok());
+        assert!(matches!(
+            scope.prepare_sidecar_with_env("env", "bin/env", list(&[]), env("high")),
+            Err(Error::EnvValidation { .. })
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_env_hardened() {
+        let scope = scope(ScopeAllowedCommand {
+            env: Some(
+                [("LEVEL".to_string(), Regex::new(r"^\d$").unwrap())]
+                    .into_iter()
+                    .collect(),
+            ),
+            hardening: Hardening {
+                strip_dangerous_env: true,
+                ..Default::default()
+            },
+            ..command("env")
+        });
+        std::env::set_var("PERL5OPT", "-w");
+        let caller_env: HashMap<_, _> = [("LEVEL".to_string(), "3".to_string())].into();
+
+        let resolved = scope.resolve_env("cmd", caller_env.clone()).unwrap();
+        assert_eq!(resolved.get("LEVEL").map(String::as_str), Some("3"));
+        assert!(!resolved.contains_key("PERL5OPT"));
+
+        let prepared = scope
+            .prepare_with_env("cmd", list(&["-0"]), caller_env)
+            .unwrap();
+        let output = tauri::async_runtime::block_on(prepared.output()).unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut received: Vec<_> = stdout
+            .split('\0')
+            .filter_map(|var| var.split_once('='))
+            .map(|(name, _)| name.trim_start_matches('\n'))
+            .collect();
+        received.sort_unstable();
+        let mut expected: Vec<_> = resolved.keys().map(String::as_str).collect();
+        expected.sort_unstable();
+        assert_eq!(received, expected);
+
+        let denied = scope.resolve_env("cmd", [("PATH".to_string(), "/tmp".to_string())].into());
+        assert!(matches!(denied, Err(Error::EnvNotAllowed { .. })));
+
+        // the hardened preset anchors the validators of the variables
+        let base = self::scope(ScopeAllowedCommand {
+            env: Some([("MODE".to_string(), Regex::new(r"\w+").unwrap())].into()),
+            ..command("env")
+        });
+        let hardened = Scope {
+            config: ScopeConfig::hardened(base.config.clone()).unwrap(),
+            ..base
+        };
+        let mode = |value: &str| [("MODE".to_string(), value.to_string())].into();
+        assert!(hardened.resolve_env("cmd", mode("ok")).is_ok());
+        assert!(matches!(
+            hardened.resolve_env("cmd", mode("ok;evil")),
+            Err(Error::EnvValidation { .. })
+        ));
+    }
+
+    #[cfg(feature = "signed-config")]
+    #[test]
+    fn test_from_file_signed() {
+        let path = std::env::temp_dir().join("tauri-plugin-shell-test-signed.json");
+        let signing_key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let public_key = signing_key.verifying_key();
+
+        std::fs::write(&path, r#"{ "scope": [{ "name": "cmd", "cmd": "ls" }] }"#).unwrap();
+        ScopeConfig::sign_file(&path, &signing_key).unwrap();
+        let config = ScopeConfig::from_file_signed(&path, &public_key).unwrap();
+        assert_eq!(config.scopes["cmd"].command, PathBuf::from("ls"));
+
+        std::fs::write(&path, r#"{ "scope": [{ "name": "cmd", "cmd": "rm" }] }"#).unwrap();
+        let tampered = ScopeConfig::from_file_signed(&path, &public_key);
+        assert!(matches!(tampered, Err(Error::InvalidSignature(_))));
+
+        // a signed config can still be invalid
+        std::fs::write(&path, r#"{ "open": "(" }"#).unwrap();
+        ScopeConfig::sign_file(&path, &signing_key).unwrap();
+        let invalid = ScopeConfig::from_file_signed(&path, &public_key);
+        assert!(matches!(invalid, Err(Error::InvalidConfig(_))));
+
+        let _ = std::fs::remove_file(signature_path(&path));
+        let _ = std::fs::remove_file(path);
+    }
+
+    fn range_command() -> ScopeAllowedCommand {
+        ScopeAllowedCommand {
+            args: Some(vec![
+                ScopeAllowedArg::var_str(r"^\d+$").unwrap(),
+                ScopeAllowedArg::Fixed("-".into()),
+                ScopeAllowedArg::var_str(r"^\d+$").unwrap(),
+            ]),
+            cross_validators: vec![CrossValidator {
+                left: 0,
+                relation: Relation::Lt,
+                right: 2,
+            }],
+            ..command("cmd")
+        }
+    }
+
+    #[test]
+    fn test_cross_validators() {
+        let scope = scope(range_command());
+        assert!(scope.validate("cmd", list(&["1", "-", "2"]), false).is_ok());
+
+        let violated = scope.validate("cmd", list(&["2", "-", "1"]), false);
+        assert!(matches!(
+            violated,
+            Err(Error::CrossValidation {
+                left: 0,
+                right: 2,
+                relation: Relation::Lt,
+                ..
+            })
+        ));
+        assert_eq!(
+            violated.unwrap_err().to_string(),
+            "Scoped command cmd arguments at positions 0 and 2 must satisfy 0 < 2"
+        );
+    }
+
+    #[test]
+    fn test_cross_validators_out_of_range() {
+        let config = |command| merge_config(scope(command).config);
+        assert!(config(range_command()).is_ok());
+
+        let mut out_of_range = range_command();
+        out_of_range.cross_validators[0].right = 3;
+        assert!(matches!(config(out_of_range), Err(Error::InvalidConfig(_))));
+
+        // a command accepting any arguments has no positions to reference
+        let any_args = ScopeAllowedCommand {
+            args: None,
+            ..range_command()
+        };
+        assert!(matches!(config(any_args), Err(Error::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_reveal() {
+        let mut scope = scope(command("cmd"));
+        let denied = scope.reveal("/tmp");
+        assert!(matches!(denied, Err(Error::RevealDenied(_))));
+
+        scope.config.reveal = Some(Regex::new(r"^/allowed/").unwrap());
+        let failing = scope.reveal("/tmp");
+        assert!(matches!(failing, Err(Error::RevealValidation { .. })));
+        // the validation passes, but there is nothing to reveal
+        let missing = scope.reveal("/allowed/tauri-plugin-shell-missing");
+        assert!(matches!(missing, Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_reveal_on_failure() {
+        let scope = scope(command("cmd"));
+        let path = "/tmp";
+        assert!(scope.reveal_on_failure(path, Ok(())).is_ok());
+
+        // a failed validation of the open path is not recovered from
+        let validation = Error::Validation {
+            index: 0,
+            validation: String::new(),
+        };
+        let failing = scope.reveal_on_failure(path, Err(validation));
+        assert!(matches!(failing, Err(Error::Validation { .. })));
+
+        let no_handler = Err(Error::NoHandler(path.into()));
+        match scope.reveal_on_failure(path, no_handler) {
+            Err(Error::OpenOrReveal { open, reveal }) => {
+                assert!(matches!(*open, Error::NoHandler(_)));
+                assert!(matches!(*reveal, Error::RevealDenied(_)));
+            }
+            result => panic!("unexpected result {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_audit_sink() {
+        let mut scope = scope(strict_command());
+        scope.config.open = Some(Regex::new(r"^https://").unwrap());
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let events_ = events.clone();
+        scope.set_audit_sink(Arc::new(move |event: AuditEvent| {
+            events_.lock().unwrap().push(event)
+        }));
+        let taken = || std::mem::take(&mut *events.lock().unwrap());
+
+        assert!(scope.prepare("cmd", list(&["-v", "value"])).is_ok());
+        assert!(taken().is_empty());
+        assert!(scope.prepare("cmd", list(&["-v", "a b"])).is_err());
+        assert!(scope.open("file:///etc/passwd", None).is_err());
+        assert!(scope.reveal("/tmp").is_err());
+        let events = taken();
+        let actions: Vec<_> = events
+            .iter()
+            .map(|event| (event.action, event.args.clone()))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                (AuditAction::Execute, vec!["-v".into(), "a b".into()]),
+                (AuditAction::Open, vec!["file:///etc/passwd".into()]),
+                (AuditAction::Reveal, vec!["/tmp".into()]),
+            ]
+        );
+        assert!(events
+            .iter()
+            .all(|event| matches!(event.outcome, AuditOutcome::Denied(_))));
+
+        scope.set_audit_successes(true);
+        assert!(scope.prepare("cmd", list(&["-v", "value"])).is_ok());
+        let events = taken();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].outcome, AuditOutcome::Allowed);
+    }
+
+    #[test]
+    fn test_audit_sink_reentrant() {
+        let scope = scope(strict_command());
+        let scope_ = scope.clone();
+        // replacing the sink from the sink itself needs the lock to be released
+        scope.set_audit_sink(Arc::new(move |_| scope_.set_audit_sink(Arc::new(|_| {}))));
+        assert!(scope.prepare("cmd", list(&["-v", "a b"])).is_err());
+        assert!(scope.prepare("cmd", list(&["-v", "a b"])).is_err());
+    }
+
+    #[test]
+    fn test_from_shell_str() {
+        let split = ExecuteArgs::from_shell_str(r#"commit -m "a message" 'it''s'"#).unwrap();
+        assert!(matches!(
+            split,
+            ExecuteArgs::List(args) if args == ["commit", "-m", "a message", "its"]
+        ));
+        let unterminated = ExecuteArgs::from_shell_str(r#"commit -m "a message"#);
+        assert!(matches!(unterminated, Err(Error::ShellSplit(_))));
+    }
+
+    #[test]
+    fn test_single_arg_mode() {
+        let single = || ExecuteArgs::Single("-v 'value'".into());
+        let rejecting = scope(strict_command());
+        assert_eq!(SingleArgMode::default(), SingleArgMode::Reject);
+        let rejected = rejecting.validate("cmd", single(), false);
+        assert!(matches!(rejected, Err(Error::InvalidInput(_))));
+
+        let splitting = scope(ScopeAllowedCommand {
+            single_arg_mode: SingleArgMode::SplitShell,
+            ..strict_command()
+        });
+        let argv = splitting.validate("cmd", single(), false).unwrap();
+        assert_eq!(argv, ["-v", "value"]);
+        let unterminated = ExecuteArgs::Single("-v 'value".into());
+        let unterminated = splitting.validate("cmd", unterminated, false);
+        assert!(matches!(unterminated, Err(Error::ShellSplit(_))));
+    }
+
+    #[test]
+    fn test_variable_positions() {
+        let mut scope = scope(named_command());
+        scope.config.scopes.insert("any".into(), command("any"));
+        assert_eq!(scope.variable_positions("cmd"), Some(vec![0, 2]));
+        assert_eq!(scope.variable_positions("any"), Some(vec![]));
+        assert_eq!(scope.variable_positions("missing"), None);
+    }
+
+    #[test]
+    fn test_file_size_max_relative() {
+        let dir = std::env::temp_dir().join("tauri-plugin-shell-test-file-size-max");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("file.txt"), "0123456789").unwrap();
+        let sized = |bytes| {
+            let scope = scope(ScopeAllowedCommand {
+                args: Some(vec![ScopeAllowedArg::FileSizeMax { bytes }]),
+                ..command("cmd")
+            });
+            scope
+                .cwds
+                .write()
+                .unwrap()
+                .insert("cmd".into(), dir.clone());
+            scope
+        };
+
+        // relative to the working directory of the command, not the one of the app
+        let argv = sized(10)
+            .validate("cmd", list(&["file.txt"]), false)
+            .unwrap();
+        assert_eq!(argv, ["file.txt"]);
+        let oversize = sized(9).validate("cmd", list(&["file.txt"]), false);
+        assert!(matches!(oversize, Err(Error::Validation { index: 0, .. })));
+        let missing = sized(10).validate("cmd", list(&["missing.txt"]), false);
+        assert!(matches!(missing, Err(Error::Io(_))));
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    fn passthrough_command() -> ScopeAllowedCommand {
+        ScopeAllowedCommand {
+            args: Some(vec![
+                ScopeAllowedArg::Fixed("-v".into()),
+                ScopeAllowedArg::var_str(r"^[\w-]+$").unwrap(),
+            ]),
+            passthrough_after: Some("--".into()),
+            ..command("cmd")
+        }
+    }
+
+    #[test]
+    fn test_passthrough_after() {
+        let scope = scope(passthrough_command());
+        let argv = scope
+            .validate("cmd", list(&["-v", "value", "--", "--all", "a b"]), false)
+            .unwrap();
+        assert_eq!(argv, ["-v", "value", "--", "--all", "a b"]);
+
+        // the variable can have the marker as its value
+        let argv = scope
+            .validate("cmd", list(&["-v", "--", "--", "x"]), false)
+            .unwrap();
+        assert_eq!(argv, ["-v", "--", "--", "x"]);
+
+        let argv = scope.validate("cmd", list(&["-v", "--"]), false).unwrap();
+        assert_eq!(argv, ["-v", "--"]);
+    }
+
+    #[test]
+    fn test_passthrough_control_chars() {
+        let scope = scope(passthrough_command());
+        let args = list(&["-v", "value", "--", "ok", "bell\u{7}"]);
+        let err = scope.validate("cmd", args, false).unwrap_err();
+        assert!(matches!(err, Error::Validation { index: 4, .. }));
+    }
+
+    #[cfg(feature = "open-checked")]
+    #[test]
+    fn test_check_reachable() {
+        use std::io::{Read, Write};
+
+        assert!(check_reachable("http://127.0.0.1:1/", None).is_ok());
+        assert!(check_reachable("http://[::1]:1/", None).is_ok());
+        assert!(check_reachable("mailto:someone", None).is_ok());
+        assert!(matches!(
+            check_reachable("http://host.invalid/", None),
+            Err(Error::Unreachable { .. })
+        ));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            // an error status still means the host answered
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+        });
+        let timeout = Some(std::time::Duration::from_secs(5));
+        assert!(check_reachable(&url, timeout).is_ok());
+        server.join().unwrap();
+        // nothing listens anymore
+        assert!(matches!(
+            check_reachable(&url, timeout),
+            Err(Error::Unreachable { .. })
+        ));
+    }
+
+    /// Resolves `$BASE` to `base`, like a base directory variable of the path resolver.
+    fn resolve_base(base: &str) -> impl Fn(&std::path::Path) -> Option<PathBuf> + '_ {
+        move |path| {
+            let path = path.to_str()?;
+            if !path.starts_with('$') {
+                return Some(path.into());
+            }
+            path.strip_prefix("$BASE")
+                .map(|rest| PathBuf::from(format!("{base}{rest}")))
+        }
+    }
+
+    #[test]
+    fn test_reresolve_paths() {
+        let scope = scope(ScopeAllowedCommand {
+            cwd: CwdMode::Path("$BASE/work".into()),
+            ..command("$BASE/bin/tool")
+        });
+        let resolved = |scope: &Scope| {
+            (
+                scope.paths.read().unwrap()["cmd"].clone(),
+                scope.cwds.read().unwrap().get("cmd").cloned(),
+            )
+        };
+
+        scope.resolve_paths_with(resolve_base("/opt")).unwrap();
+        assert_eq!(
+            resolved(&scope),
+            ("/opt/bin/tool".into(), Some("/opt/work".into()))
+        );
+        // the installation moved
+        scope
+            .resolve_paths_with(resolve_base("/usr/local"))
+            .unwrap();
+        assert_eq!(
+            resolved(&scope),
+            ("/usr/local/bin/tool".into(), Some("/usr/local/work".into()))
+        );
+
+        // unresolved paths keep their configured value, and the working directory is inherited
+        scope.resolve_paths_with(|_| None).unwrap();
+        assert_eq!(resolved(&scope), ("$BASE/bin/tool".into(), None));
+    }
+
+    #[test]
+    fn test_strict_paths() {
+        let mut scope = scope(command("$BASE/bin/tool"));
+        scope.config.strict_paths = true;
+        scope.resolve_paths_with(resolve_base("/opt")).unwrap();
+
+        let unresolved = scope.resolve_paths_with(|_| None);
+        assert!(matches!(
+            unresolved,
+            Err(Error::UnresolvedPath { command, path })
+                if command == "cmd" && path == std::path::Path::new("$BASE/bin/tool")
+        ));
+        // the previous paths are kept
+        assert_eq!(
+            scope.paths.read().unwrap()["cmd"],
+            std::path::Path::new("/opt/bin/tool")
+        );
+
+        // sidecars are resolved when they are prepared
+        scope.config.scopes.get_mut("cmd").unwrap().sidecar = true;
+        assert!(scope.resolve_paths_with(|_| None).is_ok());
+
+        scope.config.scopes.get_mut("cmd").unwrap().cwd = CwdMode::AppDir;
+        assert!(matches!(
+            scope.resolve_paths_with(resolve_base("/opt")),
+            Err(Error::UnresolvedPath { path, .. }) if path == std::path::Path::new("$APPDATA")
+        ));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_spawn_interactive() {
+        let mut scope = scope(command("cat"));
+        assert!(matches!(
+            scope.spawn_interactive("cmd", list(&[])),
+            Err(crate::Error::Scope(Error::StdinNotAllowed(name))) if name == "cmd"
+        ));
+        assert!(matches!(
+            scope.spawn_interactive("missing", list(&[])),
+            Err(crate::Error::Scope(Error::NotFound(_)))
+        ));
+
+        scope.config.scopes.get_mut("cmd").unwrap().allow_stdin = true;
+        let mut child = scope.spawn_interactive("cmd", list(&[])).unwrap();
+        child.write_stdin(b"first\n").unwrap();
+        tauri::async_runtime::block_on(async {
+            match child.recv().await {
+                Some(CommandEvent::Stdout(line)) => assert_eq!(line, b"first"),
+                event => panic!("unexpected event {event:?}"),
+            }
+            child.write_stdin(b"second\n").unwrap();
+            child.close_stdin();
+            assert!(child.write_stdin(b"late\n").is_err());
+
+            let mut lines = Vec::new();
+            let mut code = None;
+            while let Some(event) = child.recv().await {
+                match event {
+                    CommandEvent::Stdout(line) => lines.push(line),
+                    CommandEvent::Terminated(payload) => code = payload.code,
+                    _ => {}
+                }
+            }
+            assert_eq!(lines, [b"second"]);
+            // cat exits once its stdin is closed
+            assert_eq!(code, Some(0));
+        });
+    }
+
+    #[test]
+    fn test_any_of() {
+        let scope = arg_scope(ScopeAllowedArg::AnyOf(vec![
+            Regex::new(r"^\d+$").unwrap(),
+            Regex::new(r"^[a-z]+$").unwrap(),
+        ]));
+        let validate = |value: &str| scope.validate("cmd", list(&[value]), false);
+
+        assert_eq!(validate("42").unwrap(), ["42"]);
+        assert_eq!(validate("abc").unwrap(), ["abc"]);
+        assert!(matches!(
+            validate("4b"),
+            Err(Error::Validation { index: 0, validation }) if validation == r"any of [^\d+$, ^[a-z]+$]"
+        ));
+        assert!(matches!(
+            scope.validate("cmd", list(&[]), false),
+            Err(Error::MissingVar(0, _))
+        ));
+    }
+
+    #[test]
+    fn test_alias() {
+        let scope = arg_scope(ScopeAllowedArg::Alias(
+            [
+                ("quiet".to_string(), Vec::new()),
+                ("high".to_string(), vec!["-v".to_string(), "-v".to_string()]),
+            ]
+            .into_iter()
+            .collect(),
+        ));
+        let validate = |value: &str| scope.validate("cmd", list(&[value]), false);
+
+        assert_eq!(validate("high").unwrap(), ["-v", "-v"]);
+        assert!(validate("quiet").unwrap().is_empty());
+        // the mapped flags are not accepted themselves
+        assert!(matches!(
+            validate("-v"),
+            Err(Error::Validation { index: 0, validation }) if validation == "one of [high, quiet]"
+        ));
+    }
+
+    #[cfg(feature = "policy-testing")]
+    #[test]
+    fn test_check_cases() {
+        let scope = scope(strict_command());
+        let not_found = Error::NotFound("missing".into()).to_string();
+        let cases: Vec<TestCase> = serde_json::from_value(serde_json::json!([
+            { "command": "cmd", "args": ["-v", "value"], "expected": "allow" },
+            { "command": "cmd", "args": ["-v", "a b"], "expected": "deny" },
+            { "command": "missing", "expected": { "error": not_found } },
+            // wrongly expected outcomes
+            { "command": "cmd", "args": ["-v", "value"], "expected": "deny" },
+            { "command": "cmd", "expected": { "error": "another error" } }
+        ]))
+        .unwrap();
+
+        let report = scope.check_cases(&cases);
+        assert_eq!(report.passed, 3);
+        assert!(!report.is_success());
+        let failures: Vec<_> = report
+            .failures
+            .iter()
+            .map(|failure| (failure.index, failure.error.is_some()))
+            .collect();
+        assert_eq!(failures, [(3, false), (4, true)]);
+    }
+
+    #[test]
+    fn test_max_len() {
+        let scope = arg_scope(ScopeAllowedArg::Var {
+            validator: Regex::new(r"^.*$").unwrap(),
+            max_len: Some(4),
+            require_captures: Vec::new(),
+            to_native_path: false,
+            name: None,
+        });
+        let validate = |value: &str| scope.validate("cmd", list(&[value]), false);
+
+        assert_eq!(validate("abcd").unwrap(), ["abcd"]);
+        assert!(matches!(
+            validate("abcde"),
+            Err(Error::Validation { index: 0, validation }) if validation == "at most 4 bytes long"
+        ));
+        // the length is counted in bytes
+        assert!(validate("éé").is_ok());
+        assert!(validate("ééé").is_err());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_forward_events() {
+        let scope = scope(command("echo"));
+        let (rx, _) = scope
+            .prepare("cmd", list(&["hello"]))
+            .unwrap()
+            .spawn()
+            .unwrap();
+
+        let emitted = std::sync::Mutex::new(Vec::new());
+        tauri::async_runtime::block_on(forward_events(rx, |payload| {
+            emitted
+                .lock()
+                .unwrap()
+                .push(serde_json::to_value(payload).unwrap());
+        }));
+        let emitted = emitted.into_inner().unwrap();
+        // the payloads are emitted as the frontend receives them
+        assert_eq!(
+            emitted[0],
+            serde_json::json!({ "event": "Stdout", "payload": b"hello".to_vec() })
+        );
+        assert_eq!(emitted.last().unwrap()["event"], "Terminated");
+        assert_eq!(emitted.last().unwrap()["payload"]["code"], 0);
+    }
+
+    #[test]
+    fn test_uuid() {
+        let v4 = "9B2E6C3A-5F1D-4E8B-A9C7-0D3F2E1B4A6C";
+        let any = arg_scope(ScopeAllowedArg::Uuid { version: None });
+        // passed on in the canonical form
+        assert_eq!(
+            any.validate("cmd", list(&[v4]), false).unwrap(),
+            ["9b2e6c3a-5f1d-4e8b-a9c7-0d3f2e1b4a6c"]
+        );
+        assert_eq!(
+            any.validate("cmd", list(&["9b2e6c3a5f1d4e8ba9c70d3f2e1b4a6c"]), false)
+                .unwrap(),
+            ["9b2e6c3a-5f1d-4e8b-a9c7-0d3f2e1b4a6c"]
+        );
+        for invalid in ["not-a-uuid", "9b2e6c3a-5f1d-4e8b-09c7-0d3f2e1b4a6c", ""] {
+            assert!(matches!(
+                any.validate("cmd", list(&[invalid]), false),
+                Err(Error::Validation { index: 0, validation }) if validation == "UUID"
+            ));
+        }
+
+        let v1 = arg_scope(ScopeAllowedArg::Uuid { version: Some(1) });
+        assert!(matches!(
+            v1.validate("cmd", list(&[v4]), false),
+            Err(Error::Validation { validation, .. }) if validation == "version 1 UUID"
+        ));
+        assert!(v1
+            .validate(
+                "cmd",
+                list(&["c232ab00-9414-11ec-b3c8-9f6bdeced846"]),
+                false
+            )
+            .is_ok());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_singleton_released_on_exit() {
+        let mut scope = scope(ScopeAllowedCommand {
+            singleton: true,
+            ..command("true")
+        });
+        let run = |scope: &Scope| {
+            let (mut rx, _) = scope.prepare("cmd", list(&[])).unwrap().spawn().unwrap();
+            tauri::async_runtime::block_on(async { while rx.recv().await.is_some() {} });
+        };
+
+        run(&scope);
+        // the previous instance exited, so the singleton may run again
+        assert_eq!(scope.running_count(), 0);
+        run(&scope);
+
+        // other commands run concurrently
+        scope.config.scopes.get_mut("cmd").unwrap().singleton = false;
+        scope.config.scopes.get_mut("cmd").unwrap().command = "sleep".into();
+        let (_rx, first) = scope
+            .prepare("cmd", list(&["10"]))
+            .unwrap()
+            .spawn()
+            .unwrap();
+        let (_rx, second) = scope
+            .prepare("cmd", list(&["10"]))
+            .unwrap()
+            .spawn()
+            .unwrap();
+        assert_eq!(scope.running_count(), 2);
+        first.kill().unwrap();
+        second.kill().unwrap();
+    }
+
+    #[test]
+    fn test_email() {
+        let scope = arg_scope(ScopeAllowedArg::Email);
+        let validate = |value: &str| scope.validate("cmd", list(&[value]), false);
+
+        // only the domain is case-insensitive
+        assert_eq!(
+            validate("Jane.Doe@Example.COM").unwrap(),
+            ["Jane.Doe@example.com"]
+        );
+        for invalid in [
+            "Jane <jane@example.com>",
+            "jane",
+            "jane@",
+            "@example.com",
+            "jane@@example.com",
+        ] {
+            assert!(
+                matches!(
+                    validate(invalid),
+                    Err(Error::Validation { index: 0, validation }) if validation == "email address"
+                ),
+                "{invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_arg_middleware() {
+        let scope = scope(strict_command());
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let calls_ = calls.clone();
+        scope.add_arg_middleware(Box::new(move |command, mut args| {
+            calls_.fetch_add(1, Ordering::SeqCst);
+            assert_eq!(command, "cmd");
+            args.push("--color=never".into());
+            Ok(args)
+        }));
+        // middlewares run in the order they were added
+        scope.add_arg_middleware(Box::new(|_, args| {
+            if args.iter().any(|arg| arg == "forbidden") {
+                Err(Error::InvalidInput("forbidden value".into()))
+            } else {
+                Ok(args.into_iter().map(|arg| arg.to_uppercase()).collect())
+            }
+        }));
+
+        let prepared = scope.prepare("cmd", list(&["-v", "value"])).unwrap();
+        assert_eq!(
+            prepared.spawned_info().args,
+            ["-V", "VALUE", "--COLOR=NEVER"]
+        );
+        assert!(matches!(
+            scope.prepare("cmd", list(&["-v", "forbidden"])),
+            Err(Error::InvalidInput(_))
+        ));
+        // arguments failing validation never reach the middlewares
+        assert!(scope.prepare("cmd", list(&["-v", "a b"])).is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_launch_status() {
+        let launched = launch(std::process::Command::new("true")).unwrap();
+        assert_eq!(
+            launched,
+            OpenStatus::Launched {
+                launcher: "true".into()
+            }
+        );
+        assert!(launched.into_result().is_ok());
+
+        let mut command = std::process::Command::new("sh");
+        command.args(["-c", "exit 3"]);
+        let failed = launch(command).unwrap();
+        assert_eq!(
+            failed,
+            OpenStatus::Failed {
+                launcher: "sh".into(),
+                code: Some(3)
+            }
+        );
+        assert!(matches!(
+            failed.into_result(),
+            Err(Error::LaunchFailed { launcher, code: Some(3) }) if launcher == "sh"
+        ));
+
+        // a launcher that cannot be spawned is an error of its own
+        let missing = launch(std::process::Command::new("tauri-plugin-shell-missing"));
+        assert_eq!(missing.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_require_captures() {
+        let scope = arg_scope(ScopeAllowedArg::Var {
+            validator: Regex::new(r"^(?P<user>\w*)@(?P<host>[\w.]+)(?::(?P<port>\d+))?$").unwrap(),
+            max_len: None,
+            require_captures: vec!["user".into(), "host".into()],
+            to_native_path: false,
+            name: None,
+        });
+        let validate = |value: &str| scope.validate("cmd", list(&[value]), false);
+
+        assert_eq!(validate("me@example.com").unwrap(), ["me@example.com"]);
+        // optional captures may be missing
+        assert!(validate("me@example.com:22").is_ok());
+        // a required capture must not be empty
+        assert!(matches!(
+            validate("@example.com"),
+            Err(Error::Validation { index: 0, validation }) if validation.ends_with("with a non-empty `user` capture")
+        ));
+        assert!(matches!(
+            validate("example.com"),
+            Err(Error::Validation { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut scope = scope(ScopeAllowedCommand {
+            tags: vec!["network".into(), "tools".into()],
+            ..command("curl")
+        });
+        scope.config.scopes.insert(
+            "git".into(),
+            ScopeAllowedCommand {
+                tags: vec!["tools".into()],
+                ..command("git")
+            },
+        );
+        scope.config.scopes.insert("ls".into(), command("ls"));
+
+        let by_tag = scope.commands_by_tag();
+        assert_eq!(by_tag.len(), 2);
+        assert_eq!(by_tag["network"], ["cmd"]);
+        assert_eq!(by_tag["tools"], ["cmd", "git"]);
+
+        // a command is denied if any of its tags is disabled
+        scope.set_tag_enabled("network", false);
+        assert!(matches!(
+            scope.validate("cmd", list(&[]), false),
+            Err(Error::Denied { command, tag }) if command == "cmd" && tag == "network"
+        ));
+        assert!(scope.validate("git", list(&[]), false).is_ok());
+        assert!(scope.validate("ls", list(&[]), false).is_ok());
+
+        scope.set_tag_enabled("network", true);
+        assert!(scope.validate("cmd", list(&[]), false).is_ok());
+    }
+
+    #[test]
+    fn test_hostname() {
+        let names = arg_scope(ScopeAllowedArg::Hostname { allow_ip: false });
+        let validate = |value: &str| names.validate("cmd", list(&[value]), false);
+
+        assert_eq!(validate("Example.COM").unwrap(), ["example.com"]);
+        assert!(validate("localhost").is_ok());
+        assert!(validate("a-b.c1").is_ok());
+        let long_label = "a".repeat(64);
+        for invalid in [
+            "",
+            "-example.com",
+            "example-.com",
+            "exa_mple.com",
+            "example..com",
+            "1.2.3.4",
+            "example.123",
+            long_label.as_str(),
+        ] {
+            assert!(
+                matches!(
+                    validate(invalid),
+                    Err(Error::Validation { validation, .. }) if validation == "hostname"
+                ),
+                "{invalid}"
+            );
+        }
+        // at most 253 bytes
+        assert!(is_hostname(&["a"; 127].join(".")));
+        assert!(!is_hostname(&["a"; 128].join(".")));
+
+        let with_ip = arg_scope(ScopeAllowedArg::Hostname { allow_ip: true });
+        let validate = |value: &str| with_ip.validate("cmd", list(&[value]), false);
+        assert_eq!(validate("127.0.0.1").unwrap(), ["127.0.0.1"]);
+        // IPv6 addresses are passed on in their canonical form
+        assert_eq!(validate("0:0:0:0:0:0:0:1").unwrap(), ["::1"]);
+        assert!(matches!(
+            validate("1.2.3"),
+            Err(Error::Validation { validation, .. }) if validation == "hostname or IP address"
+        ));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_pipeline_preflight() {
+        let mut scope = scope(ScopeAllowedCommand {
+            singleton: true,
+            ..command("sleep")
+        });
+        scope.config.scopes.insert("echo".into(), command("echo"));
+        let stage = |command: &str, args: &[&str]| PipelineStage {
+            command: command.into(),
+            args: list(args),
+            sidecar: false,
+        };
+
+        assert!(scope
+            .pipeline_preflight(&[stage("cmd", &["1"]), stage("echo", &[]), stage("echo", &[])])
+            .is_ok());
+        // a singleton cannot run in two stages at the same time
+        assert!(matches!(
+            scope.pipeline_preflight(&[stage("cmd", &["1"]), stage("cmd", &["2"])]),
+            Err(Error::AlreadyRunning(name)) if name == "cmd"
+        ));
+        assert!(matches!(
+            scope.pipeline_preflight(&[stage("echo", &[]), stage("missing", &[])]),
+            Err(Error::NotFound(_))
+        ));
+
+        let (_rx, child) = scope
+            .prepare("cmd", list(&["10"]))
+            .unwrap()
+            .spawn()
+            .unwrap();
+        assert!(matches!(
+            scope.pipeline_preflight(&[stage("cmd", &["1"])]),
+            Err(Error::AlreadyRunning(_))
+        ));
+        // nothing was spawned by the checks
+        assert_eq!(scope.running_count(), 1);
+        child.kill().unwrap();
+    }
+
+    #[test]
+    fn test_cwd_mode() {
+        let mut scope = scope(command("ls"));
+        let prepared_cwd = |scope: &Scope| {
+            scope
+                .resolve_paths_with(|path| {
+                    let path = path.to_str()?;
+                    Some(
+                        path.replace("$APPDATA", "/data")
+                            .replace("$RESOURCE", "/res")
+                            .into(),
+                    )
+                })
+                .unwrap();
+            scope.prepare("cmd", list(&[])).unwrap().spawned_info().cwd
+        };
+
+        assert_eq!(prepared_cwd(&scope), None);
+        for (mode, expected) in [
+            (CwdMode::AppDir, "/data"),
+            (CwdMode::ResourceDir, "/res"),
+            (CwdMode::Path("$APPDATA/work".into()), "/data/work"),
+        ] {
+            scope.config.scopes.get_mut("cmd").unwrap().cwd = mode;
+            assert_eq!(prepared_cwd(&scope), Some(expected.into()));
+        }
+        // an explicit inherit does not keep a previously resolved directory
+        scope.config.scopes.get_mut("cmd").unwrap().cwd = CwdMode::Inherit;
+        assert_eq!(prepared_cwd(&scope), None);
+
+        // the commands of a group use its directory unless they set their own
+        let group = CommandGroupDefaults {
+            cwd: Some(CwdMode::ResourceDir),
+            ..Default::default()
+        };
+        scope.config.groups.insert("tools".into(), group);
+        for name in ["cmd", "own"] {
+            let command = ScopeAllowedCommand {
+                group: Some("tools".into()),
+                ..command("ls")
+            };
+            scope.config.scopes.insert(name.into(), command);
+        }
+        scope.config.scopes.get_mut("own").unwrap().cwd = CwdMode::AppDir;
+        scope.config = merge_config(scope.config).unwrap();
+        assert_eq!(scope.config.scopes["cmd"].cwd, CwdMode::ResourceDir);
+        assert_eq!(scope.config.scopes["own"].cwd, CwdMode::AppDir);
+        assert_eq!(prepared_cwd(&scope), Some("/res".into()));
+    }
+
+    #[test]
+    fn test_require_commands() {
+        let scope = scope(ScopeAllowedCommand {
+            tags: vec!["tools".into()],
+            ..command("echo")
+        });
+        scope.set_tag_enabled("tools", false);
+
+        // a disabled command is still configured
+        assert!(scope.require_commands(&["cmd"]).is_ok());
+        assert!(scope.require_commands(&[]).is_ok());
+        assert!(matches!(
+            scope.require_commands(&["zip", "cmd", "git"]),
+            Err(Error::MissingCapabilities(missing)) if missing == ["zip", "git"]
+        ));
+    }
+
+    #[test]
+    fn test_var_constructors() {
+        match ScopeAllowedArg::var_str(r"^\d+$").unwrap() {
+            ScopeAllowedArg::Var {
+                validator,
+                max_len,
+                require_captures,
+                to_native_path,
+                name,
+            } => {
+                assert_eq!(validator.as_str(), r"^\d+$");
+                assert_eq!(max_len, None);
+                assert!(require_captures.is_empty());
+                assert!(!to_native_path);
+                assert_eq!(name, None);
+            }
+            arg => panic!("unexpected argument {arg:?}"),
+        }
+        assert!(!ScopeAllowedArg::var(Regex::new("a").unwrap()).is_fixed());
+        assert!(matches!(
+            ScopeAllowedArg::var_str("("),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        let scope = scope(ScopeAllowedCommand {
+            args: Some(vec![ScopeAllowedArg::var_str(r"^\d+$").unwrap()]),
+            ..command("echo")
+        });
+        assert!(scope.prepare("cmd", list(&["42"])).is_ok());
+        assert!(scope.prepare("cmd", list(&["4x"])).is_err());
+    }
+
+    #[test]
+    fn test_max_commands() {
+        let mut config = scope(command("echo")).config;
+        config.max_commands = 1;
+        assert!(merge_config(config.clone()).is_ok());
+
+        config.scopes.insert("other".into(), command("echo"));
+        assert!(matches!(
+            merge_config(config),
+            Err(Error::TooManyCommands { count: 2, limit: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_to_native_path() {
+        let cases = [
+            ("a/b", "a/b"),
+            ("a\\b", "a/b"),
+            ("./a//b/", "a/b"),
+            ("a/..", "."),
+            ("a/../..", ".."),
+            ("../a", "../a"),
+            ("", "."),
+            ("/", "/"),
+            ("/..", "/"),
+            ("/a/../../b", "/b"),
+            ("//server/share", "//server/share"),
+            ("///a", "//a"),
+            ("C:", "C:"),
+            ("C:\\x\\..\\y", "C:/y"),
+            ("C:a/../..", "C:.."),
+        ];
+        for (path, expected) in cases {
+            let expected: String = expected
+                .chars()
+                .map(|c| {
+                    if c == '/' {
+                        std::path::MAIN_SEPARATOR
+                    } else {
+                        c
+                    }
+                })
+                .collect();
+            assert_eq!(to_native_path(path), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_hardened() {
+        let mut base = scope(ScopeAllowedCommand {
+            args: Some(vec![
+                ScopeAllowedArg::Fixed("-n".into()),
+                ScopeAllowedArg::var_str(r"\w+").unwrap(),
+            ]),
+            ..command("echo")
+        });
+        base.config.open = Some(Regex::new("https://example.com").unwrap());
+        let scope = Scope {
+            config: ScopeConfig::hardened(base.config.clone()).unwrap(),
+            ..base
+        };
+
+        // fixed arguments are not caller values
+        assert!(scope.prepare("cmd", list(&["-n", "value"])).is_ok());
+        assert!(matches!(
+            scope.prepare("cmd", list(&["-n", "-e"])),
+            Err(Error::Validation { index: 1, validation }) if validation == "no leading dash"
+        ));
+        assert!(matches!(
+            scope.prepare("cmd", list(&["-n", "a\0b"])),
+            Err(Error::Validation { index: 1, validation }) if validation == "no NUL byte"
+        ));
+        // the validator must match the whole value once anchored
+        assert!(matches!(
+            scope.prepare("cmd", list(&["-n", "value;rm"])),
+            Err(Error::Validation { index: 1, .. })
+        ));
+        assert!(matches!(
+            scope.prepare("cmd", list(&["-n", "value", "extra"])),
+            Err(Error::TooManyArgs {
+                count: 3,
+                max: 2,
+                ..
+            })
+        ));
+        assert!(scope.check_open("https://example.com", None).is_ok());
+        assert!(scope
+            .check_open("https://example.com.attacker.net", None)
+            .is_err());
+
+        // a pattern ending in an `x` flag comment swallows the closing anchor
+        let mut config = base_config_with_var(r"(?x)\w+ # word");
+        assert!(matches!(
+            ScopeConfig::hardened(config.clone()),
+            Err(Error::InvalidConfig(_))
+        ));
+        config.scopes.clear();
+        assert!(ScopeConfig::hardened(config).is_ok());
+    }
+
+    fn base_config_with_var(pattern: &str) -> ScopeConfig {
+        scope(ScopeAllowedCommand {
+            args: Some(vec![ScopeAllowedArg::var_str(pattern).unwrap()]),
+            ..command("echo")
+        })
+        .config
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardened_strips_env() {
+        let base = scope(ScopeAllowedCommand {
+            args: Some(Vec::new()),
+            ..command("env")
+        });
+        let scope = Scope {
+            config: ScopeConfig::hardened(base.config.clone()).unwrap(),
+            ..base
+        };
+        std::env::set_var("RUBYOPT", "-w");
+
+        let prepared = scope.prepare("cmd", list(&[])).unwrap();
+        let output = tauri::async_runtime::block_on(prepared.output()).unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(!stdout.lines().any(|line| line.starts_with("RUBYOPT=")));
+        assert!(stdout.lines().any(|line| line.starts_with("PATH=")));
+    }
+
+    #[test]
+    fn test_regex_report() {
+        let mut scope = scope(ScopeAllowedCommand {
+            args: Some(vec![
+                ScopeAllowedArg::Fixed("-v".into()),
+                ScopeAllowedArg::var_str(r"^(a+)+$").unwrap(),
+            ]),
+            ..command("cmd")
+        });
+        scope.config.open = Some(Regex::new(r"https?://\w{1,5}").unwrap());
+        let command = scope.config.scopes.get_mut("cmd").unwrap();
+        command.env = Some(
+            [
+                ("MODE".to_string(), Regex::new(r"(?i)^fast|slow$").unwrap()),
+                ("LEVEL".to_string(), Regex::new(r"^\d$").unwrap()),
+            ]
+            .into(),
+        );
+        command.progress_regex = Some(Regex::new(r"(?x) (?P<percent>\d+) % ").unwrap());
+
+        let report = scope.regex_report();
+        assert_eq!(report.len(), 5);
+        let (open, argument) = (&report[0], &report[1]);
+        let locations: Vec<_> = report[2..].iter().map(|info| &info.location).collect();
+        assert_eq!(
+            locations,
+            [
+                &RegexLocation::Env {
+                    command: "cmd".into(),
+                    name: "LEVEL".into()
+                },
+                &RegexLocation::Env {
+                    command: "cmd".into(),
+                    name: "MODE".into()
+                },
+                &RegexLocation::Progress {
+                    command: "cmd".into()
+                },
+            ]
+        );
+        assert_eq!(report[2].flags, "");
+        assert_eq!(report[3].flags, "i");
+        assert_eq!(report[4].flags, "x");
+        assert_eq!(open.flags, "");
+        // flags of groups count, disabled ones do not
+        let regex = Regex::new(r"(?s)a(?-s:b)(?mi:c)(?U)").unwrap();
+        assert_eq!(RegexInfo::new(RegexLocation::Open, &regex).flags, "imsU");
+        assert_eq!(open.location, RegexLocation::Open);
+        assert_eq!(open.repetition_depth, 1);
+        assert_eq!(open.unbounded_repetitions, 0);
+        assert!(!open.anchored_start && !open.anchored_end);
+        assert_eq!(open.min_len, Some("http://a".len()));
+
+        assert_eq!(
+            argument.location,
+            RegexLocation::Argument {
+                command: "cmd".into(),
+                index: 1,
+            }
+        );
+        assert_eq!(argument.pattern, r"^(a+)+$");
+        // nested unbounded repetitions
+        assert_eq!(argument.repetition_depth, 2);
+        assert_eq!(argument.unbounded_repetitions, 2);
+        assert!(argument.anchored_start && argument.anchored_end);
+        assert_eq!((argument.min_len, argument.max_len), (Some(1), None));
+        assert!(argument.compiled_size > 0);
+    }
+
+    #[test]
+    fn test_lenient_positions_over_supply() {
+        let scope = scope(ScopeAllowedCommand {
+            args: strict_command().args,
+            ..command("cmd")
+        });
+        let argv = scope
+            .validate("cmd", list(&["-v", "value", "extra"]), false)
+            .unwrap();
+        assert_eq!(argv, ["-v", "value"]);
     }
 }